@@ -1,14 +1,16 @@
-import sys, itertools, math
+import argparse, sys, itertools, math
 
 def main():
-    # Read file
-    filename = sys.argv[1]
-    with open(filename, 'r') as f:
-        n, m, k = (int(num) for num in f.readline().split())
-        graph = { i:[] for i in range(1, n+1) }
-        for i in range(k):
-            u, v = (int(num) for num in f.readline().split())
-            graph[u].append(v)
+    arguments = argparse.ArgumentParser(description="Compute the minimum number of pages of an album.")
+    arguments.add_argument("filename", help="the input file (see the README for the format)")
+    arguments.add_argument("--lenient", action="store_true",
+                           help="warn instead of failing when the number of edges differs from the header")
+    args = arguments.parse_args()
+    try:
+        n, m, graph = read_file(args.filename, args.lenient)
+    except (OSError, ValueError) as error:
+        print("{}: {}".format(args.filename, error), file=sys.stderr)
+        sys.exit(2)
 
     # Output
     if m <= 0:
@@ -18,6 +20,54 @@ def main():
     else:
         print("Impossible")
 
+# Read an instance as the rust version does: a header `n m k` and `k` lines `u v`,
+# where an edge can also be written `u v 1`. The directives, the weak edges and
+# the lags of more than one page are not supported.
+def read_file(filename: str, lenient: bool):
+    with open(filename, 'r') as f:
+        lines = f.read().splitlines()
+    header = lines[0].split() if lines else []
+    if len(header) != 3:
+        raise ValueError("1: the header must be `n m k`, found {} numbers".format(len(header)))
+    n, m, k = (number(token, 1) for token in header)
+    graph = { i:[] for i in range(1, n+1) }
+    n_edges = 0
+    for line_number, line in enumerate(lines[1:], 2):
+        tokens = line.split()
+        if not tokens:
+            continue
+        if tokens[0][0].isalpha():
+            raise ValueError("{}: the `{}` directive is not supported by the python version"
+                             .format(line_number, tokens[0]))
+        if len(tokens) < 2:
+            raise ValueError("{}: an edge needs two photos".format(line_number))
+        if len(tokens) > 3:
+            raise ValueError("{}: an edge has only two photos and an optional lag".format(line_number))
+        u, v = (number(token, line_number) for token in tokens[:2])
+        for photo in (u, v):
+            if not 1 <= photo <= n:
+                raise ValueError("{}: photo {} is not in 1..={}".format(line_number, photo, n))
+        if u == v:
+            raise ValueError("{}: photo {} cannot come after itself".format(line_number, u))
+        if len(tokens) == 3 and tokens[2] != "1":
+            raise ValueError("{}: weak edges and lags are not supported by the python version"
+                             .format(line_number))
+        graph[u].append(v)
+        n_edges += 1
+    # Check the number of edges against k
+    if n_edges != k:
+        message = "the header declares {} edges but {} were found".format(k, n_edges)
+        if not lenient:
+            raise ValueError(message)
+        print("warning: " + message, file=sys.stderr)
+    return n, m, graph
+
+# Read a number on line `line_number`
+def number(token: str, line_number: int) -> int:
+    if not token.isdigit():
+        raise ValueError("{}: expected a number, found `{}`".format(line_number, token))
+    return int(token)
+
 # Compute the minimal number of pages needed given constraint graph
 def min_page_feasible(graph: dict, max_by_page: int) -> int:
     n_photos = len(graph)
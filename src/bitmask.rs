@@ -0,0 +1,1013 @@
+//! Memoized dynamic program on sets of photos represented as bitmasks.
+//!
+//! This follows the same Case 1/2/3 recursion as `schedule_feasible`, but the set
+//! of photos already placed is a `u64` (bit `i` stands for photo `i + 1`), so a
+//! remaining set reached through different branches of Case 3 is solved only once.
+
+use crate::capacity::{all_pages, maximal_pages};
+use crate::count::Count;
+use crate::instance::{Instance, Schedule, Waits};
+use crate::random::Random;
+use crate::stop::Stop;
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap};
+
+/// Largest number of photos a bitmask can hold.
+pub(crate) const MAX_PHOTOS: usize = 64;
+
+/// Number of pages memoized when the pins cannot be met.
+const UNREACHABLE: usize = usize::MAX;
+
+pub(crate) struct BitmaskSolver<'a> {
+    instance: &'a Instance,
+    /// Set of all the photos.
+    all: u64,
+    /// Set of the photos of size 1 without pin nor chapter, that can fill any free spot.
+    fillers: u64,
+    /// Set of the pinned photos.
+    pinned: u64,
+    /// `predecessors[i]` is the set of photos with an edge to photo `i + 1`.
+    predecessors: Vec<u64>,
+    /// `successors[i]` is the set of photos with an edge from photo `i + 1`.
+    successors: Vec<u64>,
+    /// `weak_predecessors[i]` is the set of photos with a weak edge to photo `i + 1`.
+    weak_predecessors: Vec<u64>,
+    /// `weak_neighbours[i]` is the set of photos with a weak edge to or from photo `i + 1`.
+    weak_neighbours: Vec<u64>,
+    /// `apart[i]` is the set of photos apart from photo `i + 1`.
+    apart: Vec<u64>,
+    /// Set of the photos of each chapter.
+    chapters: BTreeMap<usize, u64>,
+    /// Sets of photos on the same spread.
+    spread_groups: Vec<u64>,
+    /// `spread_apart[i]` is the set of photos on another spread than photo `i + 1`.
+    spread_apart: Vec<u64>,
+    /// Minimum number of pages for the photos not placed in a state,
+    /// starting from a page given by `page_key`, or `UNREACHABLE`.
+    memo: HashMap<(State, usize), usize>,
+    /// Number of schedules for the photos not placed in a state, starting from a page
+    /// given by `page_key` and within a number of pages.
+    counts: HashMap<(State, usize, usize), Count>,
+    /// Limits of the search for the minimum number of pages, if any.
+    stop: Option<&'a Stop>,
+}
+
+/// What matters of the pages already filled for the next ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct State {
+    /// Set of the photos placed (down-closed).
+    placed: u64,
+    /// Photos held back by lags.
+    waits: Waits,
+    /// Chapter with photos placed and photos left.
+    chapter: Option<usize>,
+    /// Set of the photos on the previous pages of the spread of the next page.
+    spread: u64,
+}
+
+impl<'a> BitmaskSolver<'a> {
+    pub fn new(instance: &'a Instance) -> Self {
+        assert!(instance.capacities.min() > 0);
+        let graph = &instance.graph;
+        let n_photos = graph.count_vertices();
+        assert!(
+            n_photos <= MAX_PHOTOS,
+            "Too many photos for the bitmask solver"
+        );
+        let mut predecessors = vec![0; n_photos];
+        let mut successors = vec![0; n_photos];
+        for (&u, neighbourhood) in &graph.adj_list {
+            for &v in neighbourhood {
+                predecessors[v as usize - 1] |= bit(u);
+                successors[u as usize - 1] |= bit(v);
+            }
+        }
+        let mut weak_predecessors = vec![0; n_photos];
+        let mut weak_neighbours = vec![0; n_photos];
+        for (u, v) in graph.weak_edges() {
+            weak_predecessors[v as usize - 1] |= bit(u);
+            weak_neighbours[v as usize - 1] |= bit(u);
+            weak_neighbours[u as usize - 1] |= bit(v);
+        }
+        let all = if n_photos == MAX_PHOTOS {
+            u64::MAX
+        } else {
+            (1 << n_photos) - 1
+        };
+        // The last photos have a weak predecessor in every other photo.
+        let last = graph.last_photos().fold(0, |set, photo| set | bit(photo));
+        for photo in graph.last_photos() {
+            weak_predecessors[photo as usize - 1] |= all & !bit(photo);
+        }
+        for (i, neighbours) in weak_neighbours.iter_mut().enumerate() {
+            *neighbours |= if last & bit(i as u32 + 1) != 0 {
+                all & !bit(i as u32 + 1)
+            } else {
+                last
+            };
+        }
+        let mut apart = vec![0; n_photos];
+        for (u, v) in graph.apart_pairs() {
+            apart[u as usize - 1] |= bit(v);
+            apart[v as usize - 1] |= bit(u);
+        }
+        let mut chapters = BTreeMap::new();
+        for (&photo, &chapter) in &instance.chapters {
+            *chapters.entry(chapter).or_insert(0) |= bit(photo);
+        }
+        let mut spread_groups = Vec::new();
+        let mut spread_apart = vec![0; n_photos];
+        if let Some(spreads) = &instance.spreads {
+            for group in &spreads.together {
+                spread_groups.push(group.iter().fold(0, |set, &photo| set | bit(photo)));
+            }
+            for &(u, v) in &spreads.apart {
+                spread_apart[u as usize - 1] |= bit(v);
+                spread_apart[v as usize - 1] |= bit(u);
+            }
+        }
+        let spread_photos = instance
+            .spreads
+            .iter()
+            .flat_map(|spreads| spreads.photos())
+            .fold(0, |set, photo| set | bit(photo));
+        let pinned = instance.pins.keys().fold(0, |set, &photo| set | bit(photo));
+        let fillers = (1..=n_photos as u32)
+            .filter(|&photo| instance.size(photo) == 1 && instance.chapter(photo).is_none())
+            .fold(0, |set, photo| set | bit(photo))
+            & !pinned
+            & !spread_photos;
+        Self {
+            instance,
+            all,
+            fillers,
+            pinned,
+            predecessors,
+            successors,
+            weak_predecessors,
+            weak_neighbours,
+            apart,
+            chapters,
+            spread_groups,
+            spread_apart,
+            memo: HashMap::new(),
+            counts: HashMap::new(),
+            stop: None,
+        }
+    }
+    /// Stop the search for the minimum number of pages once `stop` is reached,
+    /// `min_pages` and `schedule` then returning `None` and the solver being of no more use.
+    pub fn stop(mut self, stop: &'a Stop) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+    /// Return whether the search was stopped before its end.
+    pub fn stopped(&self) -> bool {
+        self.stop.is_some_and(Stop::stopped)
+    }
+    /// Return the photos not placed whose predecessors are all placed.
+    fn roots(&self, placed: u64) -> u64 {
+        photos(self.all & !placed)
+            .filter(|&photo| self.predecessors[photo as usize - 1] & !placed == 0)
+            .fold(0, |set, photo| set | bit(photo))
+    }
+    /// Return the photos whose pin allows page `page` and not held back by `waits`.
+    fn released(&self, page: usize, waits: &[(u32, usize)]) -> u64 {
+        photos(self.pinned)
+            .filter(|&photo| !self.instance.pin(photo).allows(page))
+            .fold(self.all & !held_back(waits), |set, photo| set & !bit(photo))
+    }
+    /// Return the photos that can go on page `page`, for the chapter still open
+    /// or for each chapter that can start.
+    fn ready_sets(&self, state: &State, page: usize) -> Vec<u64> {
+        let placed = state.placed;
+        let released = self.released(page, &state.waits)
+            & !photos(state.spread)
+                .fold(0, |set, photo| set | self.spread_apart[photo as usize - 1]);
+        let labeled = self.chapters.values().fold(0, |set, &photos| set | photos);
+        let started = |chapter| self.chapters[&chapter] & placed != 0;
+        let mut result = Vec::new();
+        for chapter in self.instance.next_chapters(state.chapter, started) {
+            let chapter_photos = chapter.map_or(0, |chapter| self.chapters[&chapter]);
+            let photos_ready = self.ready(placed, released & (!labeled | chapter_photos));
+            if chapter.is_some() && chapter != state.chapter && photos_ready & chapter_photos == 0 {
+                // The chapter cannot start yet.
+                continue;
+            }
+            result.push(photos_ready);
+        }
+        result
+    }
+    /// Return if every group on the spread of page `page` is complete or untouched
+    /// once `placed` are, if the spread ends there.
+    fn spread_complete(&self, placed: u64, page: usize) -> bool {
+        !self.instance.closes_spread(page)
+            || self
+                .spread_groups
+                .iter()
+                .all(|&group| group & placed == 0 || group & !placed == 0)
+    }
+    /// Return the sets of photos that can fill page `page`: all the ready ones if they fit,
+    /// or else the maximal ones, for the chapter still open or for each chapter that can start.
+    /// With constraints on the spreads, every set that fits is tried.
+    fn choices(&self, state: &State, page: usize) -> Vec<u64> {
+        let waiting = !state.waits.is_empty() || page < self.instance.pinned_pages();
+        let closes = self.instance.closes_spread(page);
+        let mut result = Vec::new();
+        for photos_ready in self.ready_sets(state, page) {
+            if self.instance.spread_constrained() {
+                // A blank spread is useless without waiting for a lag or a pin,
+                // but a blank first page alone moves the next pages to the other side.
+                let empty_allowed = waiting || !closes || state.spread != 0 || page == 0;
+                for set in self.pages(photos_ready, page, false) {
+                    if (set != 0 || empty_allowed) && self.spread_complete(state.placed | set, page)
+                    {
+                        result.push(set);
+                    }
+                }
+            } else if photos_ready == 0 && !waiting {
+                // An empty page would not change anything.
+                continue;
+            } else if self.fits(photos_ready, self.instance.capacities.of_page(page)) {
+                // Case 2: All ready-to-use photos fit in the next page.
+                result.push(photos_ready);
+            } else {
+                // Case 3: Try all the ways to fill the next page.
+                result.extend(self.pages(photos_ready, page, true));
+            }
+        }
+        result
+    }
+    /// Return every set of photos that can fill page `page`, in increasing order
+    /// of their bitmasks.
+    fn all_choices(&self, state: &State, page: usize) -> Vec<u64> {
+        let mut sets: Vec<u64> = self
+            .ready_sets(state, page)
+            .into_iter()
+            .flat_map(|photos_ready| self.pages(photos_ready, page, false))
+            .filter(|&set| self.spread_complete(state.placed | set, page))
+            .collect();
+        sets.sort_unstable();
+        sets.dedup();
+        sets
+    }
+    /// Return every set of photos that can fill page `page` and still lead to
+    /// the minimum number of pages, in increasing order of their bitmasks.
+    fn optimal_choices(&mut self, state: &State, page: usize) -> Vec<u64> {
+        let n_pages = self.min_pages_from(state, page);
+        let mut sets = self.all_choices(state, page);
+        sets.retain(|&set| self.min_pages_with(state, page, set) == n_pages);
+        sets
+    }
+    /// Return the state for the page after page `page`, holding the photos of `set`.
+    fn next_state(&self, state: &State, page: usize, set: u64) -> State {
+        let placed = state.placed | set;
+        let waits = self
+            .instance
+            .next_waits(&state.waits, &photos(set).collect::<Vec<_>>());
+        // The chapter stays open if it has started and has photos left.
+        let chapter = state
+            .chapter
+            .or_else(|| photos(set).find_map(|photo| self.instance.chapter(photo)))
+            .filter(|chapter| self.chapters[chapter] & !placed != 0);
+        let spread = if self.instance.spread_constrained() && !self.instance.closes_spread(page) {
+            state.spread | set
+        } else {
+            0
+        };
+        State {
+            placed,
+            waits,
+            chapter,
+            spread,
+        }
+    }
+    /// Return the minimum number of pages when page `page` holds the photos of `set`.
+    fn min_pages_with(&mut self, state: &State, page: usize, set: u64) -> usize {
+        let next = self.next_state(state, page, set);
+        1_usize.saturating_add(self.min_pages_from(&next, page + 1))
+    }
+    /// Return if a photo not placed has missed its deadline at page `page`.
+    fn missed_deadline(&self, placed: u64, page: usize) -> bool {
+        photos(self.pinned & !placed).any(|photo| !self.instance.before_deadline(photo, page))
+    }
+    /// Return the same number for all the pages followed by the same capacities,
+    /// after which the pins do not change and in the same place of their spread
+    /// (the first page, which may be alone on its spread, having its own number).
+    fn page_key(&self, page: usize) -> usize {
+        if page == 0 {
+            return 0;
+        }
+        let pinned_pages = self.instance.pinned_pages();
+        let key = if page < pinned_pages {
+            page
+        } else {
+            pinned_pages + self.instance.capacities.canonical_page(page)
+        };
+        1 + 2 * key + self.instance.closes_spread(page) as usize
+    }
+    /// Return the `released` roots that can go on the next page, that is whose weak
+    /// predecessors not placed are all roots that can go on the next page.
+    fn ready(&self, placed: u64, released: u64) -> u64 {
+        let mut ready = self.roots(placed) & released;
+        loop {
+            let blocked = photos(ready)
+                .filter(|&photo| self.weak_predecessors[photo as usize - 1] & !placed & !ready != 0)
+                .fold(0, |set, photo| set | bit(photo));
+            if blocked == 0 {
+                return ready;
+            }
+            ready &= !blocked;
+        }
+    }
+    /// Return the roots of size 1 that have no edge nor apart photo left
+    /// and are not held back by `waits`.
+    fn isolated_vertices(&self, placed: u64, waits: &[(u32, usize)]) -> u64 {
+        photos(self.roots(placed) & self.fillers & !held_back(waits))
+            .filter(|&photo| {
+                let i = photo as usize - 1;
+                (self.successors[i] | self.weak_neighbours[i] | self.apart[i]) & !placed == 0
+            })
+            .fold(0, |set, photo| set | bit(photo))
+    }
+    /// Return if the photos of `set` can all go on a page of capacity `capacity`.
+    fn fits(&self, set: u64, capacity: usize) -> bool {
+        self.size(set) <= capacity
+            && photos(set).all(|photo| self.apart[photo as usize - 1] & set == 0)
+    }
+    /// Return the number of slots taken by a set of photos.
+    fn size(&self, set: u64) -> usize {
+        photos(set).map(|photo| self.instance.size(photo)).sum()
+    }
+    /// Return the sets of ready photos that fit on page `page`,
+    /// or only those to which no other one can be added.
+    fn pages(&self, ready: u64, page: usize, only_maximal: bool) -> Vec<u64> {
+        let units = self.instance.page_units(&photos(ready).collect::<Vec<_>>());
+        let capacity = self.instance.capacities.of_page(page);
+        let pages = if only_maximal {
+            maximal_pages(&units, capacity)
+        } else {
+            all_pages(&units, capacity)
+        };
+        pages
+            .into_iter()
+            .map(|photos| photos.into_iter().fold(0, |set, photo| set | bit(photo)))
+            .collect()
+    }
+    /// Compute if no directed cycle goes through a strict edge.
+    pub fn is_acyclic(&self) -> bool {
+        let mut placed = 0;
+        while placed != self.all {
+            let ready = self.ready(placed, self.all);
+            if ready == 0 {
+                return false;
+            }
+            placed |= ready;
+        }
+        true
+    }
+    /// Compute the minimum number of pages, or `None` if the graph has a cycle
+    /// or the pins cannot be met.
+    pub fn min_pages(&mut self) -> Option<usize> {
+        if self.is_acyclic()
+            && self.min_pages_from(&State::default(), 0) != UNREACHABLE
+            && !self.stopped()
+        {
+            Some(self.min_pages_from(&State::default(), 0))
+        } else {
+            None
+        }
+    }
+    /// Return the number of states solved so far.
+    pub fn n_states(&self) -> usize {
+        self.memo.len()
+    }
+    /// Compute an optimal schedule, or `None` if the graph has a cycle
+    /// or the pins cannot be met.
+    pub fn schedule(&mut self) -> Option<Schedule> {
+        if self.is_acyclic()
+            && self.min_pages_from(&State::default(), 0) != UNREACHABLE
+            && !self.stopped()
+        {
+            // The states solved are all that the schedule needs.
+            self.stop = None;
+            Some(self.schedule_from(&State::default(), 0))
+        } else {
+            None
+        }
+    }
+    /// Iterate over the optimal schedules, or return `None` if the graph has a cycle
+    /// or the pins cannot be met.
+    pub fn schedules(mut self) -> Option<Schedules<'a>> {
+        let root = State::default();
+        if self.is_acyclic() && self.min_pages_from(&root, 0) != UNREACHABLE {
+            let mut sets = self.optimal_choices(&root, 0);
+            sets.reverse();
+            Some(Schedules {
+                solver: self,
+                stack: vec![(root, sets)],
+                pages: Vec::new(),
+            })
+        } else {
+            None
+        }
+    }
+    /// Count the schedules with at most `n_pages` pages, that is the ways to assign
+    /// the photos to the first `n_pages` pages.
+    pub fn count_schedules(&mut self, n_pages: usize) -> Count {
+        if self.is_acyclic() {
+            self.count_from(&State::default(), 0, n_pages)
+        } else {
+            Count::zero()
+        }
+    }
+    /// Draw an optimal schedule uniformly at random, or return `None` if the graph
+    /// has a cycle or the pins cannot be met.
+    pub fn sample(&mut self, random: &mut Random) -> Option<Schedule> {
+        let mut state = State::default();
+        let n_pages = self.min_pages()?;
+        let mut schedule = Vec::new();
+        for page in 0..n_pages {
+            // Each set leads to as many schedules as the pages after it can hold.
+            let pages_left = n_pages - page - 1;
+            let mut drawn = self
+                .count_from(&state, page, pages_left + 1)
+                .random_below(random);
+            for set in self.all_choices(&state, page) {
+                let next = self.next_state(&state, page, set);
+                let count = self.count_from(&next, page + 1, pages_left);
+                if drawn < count {
+                    schedule.push(photos(set).collect());
+                    state = next;
+                    break;
+                }
+                drawn -= &count;
+            }
+        }
+        Some(schedule)
+    }
+    /// Count the ways to assign the photos not placed in `state` to the `n_pages` pages
+    /// from page `page` on.
+    fn count_from(&mut self, state: &State, page: usize, n_pages: usize) -> Count {
+        if state.placed == self.all {
+            // The pages left stay empty.
+            return Count::one();
+        }
+        if self.min_pages_from(state, page) > n_pages {
+            return Count::zero();
+        }
+        let key = (state.clone(), self.page_key(page), n_pages);
+        if let Some(count) = self.counts.get(&key) {
+            return count.clone();
+        }
+        let mut count = Count::zero();
+        for set in self.all_choices(state, page) {
+            let next = self.next_state(state, page, set);
+            count += &self.count_from(&next, page + 1, n_pages - 1);
+        }
+        self.counts.insert(key, count.clone());
+        count
+    }
+    /// Return the minimum number of pages for the photos not placed in `state`,
+    /// starting at page `page`, or `UNREACHABLE` if the pins, chapters or spreads cannot be met.
+    fn min_pages_from(&mut self, state: &State, page: usize) -> usize {
+        let capacities = &self.instance.capacities;
+        let key = (state.clone(), self.page_key(page));
+        if let Some(&n_pages) = self.memo.get(&key) {
+            return n_pages;
+        }
+        if self.stop.is_some_and(Stop::node) {
+            // The states left count as unreachable, and the whole result is dropped.
+            return UNREACHABLE;
+        }
+        let placed = state.placed;
+        let remaining = self.all & !placed;
+        let n_pages = if self.missed_deadline(placed, page) {
+            UNREACHABLE
+        } else if remaining == 0 || self.instance.one_by_page() {
+            remaining.count_ones() as usize
+        } else {
+            let photos_no_dependency = self.isolated_vertices(placed, &state.waits);
+            if photos_no_dependency != 0 {
+                // Case 1: Photos without dependency fill the free spots.
+                let rest = State {
+                    placed: placed | photos_no_dependency,
+                    ..state.clone()
+                };
+                max(
+                    capacities.pages_for(page, self.size(remaining)),
+                    self.min_pages_from(&rest, page),
+                )
+            } else {
+                // Cases 2 and 3
+                let mut result = UNREACHABLE;
+                for set in self.choices(state, page) {
+                    result = result.min(self.min_pages_with(state, page, set));
+                }
+                result
+            }
+        };
+        self.memo.insert(key, n_pages);
+        n_pages
+    }
+    /// Return an optimal schedule for the photos not placed in `state` starting at page `page`,
+    /// following the choices that realise `min_pages_from`.
+    fn schedule_from(&mut self, state: &State, page: usize) -> Schedule {
+        let placed = state.placed;
+        let remaining = self.all & !placed;
+        if remaining == 0 {
+            return Vec::new();
+        }
+        let capacities = &self.instance.capacities;
+        if self.instance.one_by_page() {
+            // Without cycles of weak edges, some ready photo has no weak predecessor left.
+            let photo = photos(self.ready(placed, self.all))
+                .find(|&photo| self.weak_predecessors[photo as usize - 1] & !placed == 0)
+                .unwrap();
+            let mut schedule = vec![vec![photo]];
+            schedule
+                .extend(self.schedule_from(&self.next_state(state, page, bit(photo)), page + 1));
+            return schedule;
+        }
+        let photos_no_dependency = self.isolated_vertices(placed, &state.waits);
+        let n_pages = self.min_pages_from(state, page);
+        // Case 1
+        if photos_no_dependency != 0 {
+            let rest = State {
+                placed: placed | photos_no_dependency,
+                ..state.clone()
+            };
+            let mut schedule = self.schedule_from(&rest, page);
+            schedule.resize(n_pages, Vec::new());
+            let mut free_photos = photos(photos_no_dependency);
+            for (capacity, photos) in capacities.from_page(page).zip(&mut schedule) {
+                let free_spots = capacity - self.instance.page_size(photos);
+                photos.extend(free_photos.by_ref().take(free_spots));
+            }
+            return schedule;
+        }
+        // Cases 2 and 3
+        let chosen = self
+            .choices(state, page)
+            .into_iter()
+            .find(|&set| self.min_pages_with(state, page, set) == n_pages)
+            .unwrap();
+        let next = self.next_state(state, page, chosen);
+        let mut schedule = vec![photos(chosen).collect()];
+        schedule.extend(self.schedule_from(&next, page + 1));
+        schedule
+    }
+}
+
+/// Iterator over the distinct optimal schedules of an instance, built by `Solver::schedules`.
+///
+/// Each schedule is given once, the photos of each page in increasing order.
+pub struct Schedules<'a> {
+    solver: BitmaskSolver<'a>,
+    /// For the pages filled so far and the next one: the state before the page
+    /// and the sets of photos left to try on it, the next one last.
+    stack: Vec<(State, Vec<u64>)>,
+    /// Sets of photos on the pages filled so far.
+    pages: Vec<u64>,
+}
+
+impl Iterator for Schedules<'_> {
+    type Item = Schedule;
+
+    fn next(&mut self) -> Option<Schedule> {
+        loop {
+            let (state, sets) = self.stack.last_mut()?;
+            if state.placed == self.solver.all {
+                let schedule = self
+                    .pages
+                    .iter()
+                    .map(|&set| photos(set).collect())
+                    .collect();
+                self.stack.pop();
+                self.pages.pop();
+                return Some(schedule);
+            }
+            match sets.pop() {
+                None => {
+                    self.stack.pop();
+                    self.pages.pop();
+                }
+                Some(set) => {
+                    let page = self.pages.len();
+                    let next = self.solver.next_state(state, page, set);
+                    let mut sets = self.solver.optimal_choices(&next, page + 1);
+                    sets.reverse();
+                    self.pages.push(set);
+                    self.stack.push((next, sets));
+                }
+            }
+        }
+    }
+}
+
+/// Return the singleton set containing `photo`.
+fn bit(photo: u32) -> u64 {
+    1 << (photo - 1)
+}
+
+/// Return the set of photos held back by `waits`.
+fn held_back(waits: &[(u32, usize)]) -> u64 {
+    waits.iter().fold(0, |set, &(photo, _)| set | bit(photo))
+}
+
+/// Iterate over the photos of a set in increasing order.
+fn photos(mut set: u64) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        if set == 0 {
+            None
+        } else {
+            let photo = set.trailing_zeros() + 1;
+            set &= set - 1;
+            Some(photo)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capacity::Capacities;
+    use crate::graph::DependencyGraph;
+    use crate::reference;
+    use crate::solver::Solver;
+    use std::ops::Range;
+
+    /// Deterministic pseudo-random graphs for cross-checking.
+    fn random_graph(seed: u64, n_photos: usize, n_edges: usize) -> DependencyGraph {
+        let mut state = seed;
+        let mut next = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as u32 + 1
+        };
+        let mut edges = Vec::new();
+        for _ in 0..n_edges {
+            let (u, v) = (next(n_photos), next(n_photos));
+            if u < v {
+                edges.push((u, v));
+            }
+        }
+        DependencyGraph::new(edges, n_photos)
+    }
+    /// Minimum number of pages by trying every assignment of the photos to pages.
+    fn brute_force(instance: &Instance) -> Option<usize> {
+        let n_photos = instance.graph.count_vertices();
+        let total_lag: usize = instance.graph.lagged_edges().map(|(_, _, lag)| lag).sum();
+        // With spreads, a photo may need a spread of its own.
+        let n_pages = match instance.spreads {
+            Some(_) => 2 * n_photos + 1,
+            None => n_photos,
+        } + instance.pinned_pages()
+            + total_lag;
+        (1..=n_pages).find(|&n_pages| {
+            (0..n_pages.pow(n_photos as u32)).any(|code| {
+                let mut schedule = vec![Vec::new(); n_pages];
+                let mut code = code;
+                for photo in 1..=n_photos as u32 {
+                    schedule[code % n_pages].push(photo);
+                    code /= n_pages;
+                }
+                instance.is_valid_schedule(&schedule)
+            })
+        })
+    }
+    /// Valid schedules with `n_pages` pages, by trying every assignment of the photos to pages.
+    fn brute_force_schedules(instance: &Instance, n_pages: usize) -> Vec<Schedule> {
+        let n_photos = instance.graph.count_vertices();
+        (0..n_pages.pow(n_photos as u32))
+            .map(|code| {
+                let mut schedule = vec![Vec::new(); n_pages];
+                let mut code = code;
+                for photo in 1..=n_photos as u32 {
+                    schedule[code % n_pages].push(photo);
+                    code /= n_pages;
+                }
+                schedule
+            })
+            .filter(|schedule| instance.is_valid_schedule(schedule))
+            .collect()
+    }
+    /// Check the bitmask solver against the reference implementation.
+    fn check_against_reference(instance: &Instance) {
+        let expected = reference::min_pages(instance);
+        let mut solver = BitmaskSolver::new(instance);
+        assert_eq!(solver.min_pages(), expected);
+        let schedule = solver.schedule();
+        assert_eq!(schedule.as_ref().map(Vec::len), expected);
+        if let Some(schedule) = schedule {
+            assert!(instance.is_valid_schedule(&schedule));
+        }
+    }
+
+    /// Check both solvers against the brute force on the instances drawn from each seed.
+    fn check_against_brute_force(seeds: Range<u64>, instances: impl Fn(u64) -> Vec<Instance>) {
+        for seed in seeds {
+            for instance in instances(seed) {
+                check_against_reference(&instance);
+                assert_eq!(
+                    Solver::new(&instance).min_pages().ok(),
+                    brute_force(&instance)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_examples() {
+        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
+        let instance = Instance::new(g1, 2);
+        assert_eq!(BitmaskSolver::new(&instance).min_pages(), Some(3));
+        let g3 = DependencyGraph::new(vec![], 11);
+        let instance = Instance::new(g3, 2);
+        assert_eq!(BitmaskSolver::new(&instance).min_pages(), Some(6));
+        let empty = Instance::new(DependencyGraph::new(vec![], 0), 2);
+        assert_eq!(BitmaskSolver::new(&empty).schedule(), Some(vec![]));
+        let cycle = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1)], 4);
+        let instance = Instance::new(cycle, 2);
+        assert_eq!(BitmaskSolver::new(&instance).min_pages(), None);
+    }
+    #[test]
+    fn test_against_reference() {
+        for seed in 1..60 {
+            let n_photos = 4 + seed as usize % 7;
+            let graph = random_graph(seed, n_photos, n_photos);
+            for max_by_page in 1..4 {
+                check_against_reference(&Instance::new(graph.clone(), max_by_page));
+            }
+        }
+    }
+    #[test]
+    fn test_variable_capacities_against_reference() {
+        let patterns = [
+            Capacities::new(vec![1, 2], vec![3]),
+            Capacities::new(vec![], vec![2, 3]),
+            Capacities::new(vec![3, 1], vec![1, 2]),
+        ];
+        for seed in 1..40 {
+            let n_photos = 4 + seed as usize % 6;
+            let graph = random_graph(seed, n_photos, n_photos);
+            for capacities in &patterns {
+                check_against_reference(&Instance::with_capacities(
+                    graph.clone(),
+                    capacities.clone(),
+                ));
+            }
+        }
+    }
+    #[test]
+    fn test_sizes_against_reference() {
+        for seed in 1..40 {
+            let n_photos = 4 + seed as usize % 6;
+            let graph = random_graph(seed, n_photos, n_photos / 2);
+            let capacities = Capacities::new(vec![2], vec![3, 4]);
+            let mut instance = Instance::with_capacities(graph, capacities);
+            instance.sizes = (0..n_photos).map(|i| 1 + (i * seed as usize) % 3).collect();
+            check_against_reference(&instance);
+        }
+    }
+    #[test]
+    fn test_weak_edges_against_reference() {
+        use crate::solver::Method;
+        for seed in 1..60 {
+            let n_photos = 4 + seed as usize % 6;
+            let strict = random_graph(seed, n_photos, n_photos / 2);
+            // Weak edges in both directions, making some cycles.
+            let weak = random_graph(seed * 7 + 3, n_photos, n_photos);
+            let backward = random_graph(seed * 13 + 5, n_photos, 2);
+            let graph = DependencyGraph::builder(n_photos)
+                .edges(strict.edges())
+                .weak_edges(weak.edges())
+                .weak_edges(backward.edges().map(|(u, v)| (v, u)))
+                .build()
+                .unwrap();
+            for max_by_page in 1..5 {
+                let instance = Instance::new(graph.clone(), max_by_page);
+                let expected = Solver::new(&instance).method(Method::Reference).schedule();
+                let found = Solver::new(&instance).schedule();
+                assert_eq!(
+                    found.as_ref().map(Vec::len),
+                    expected.as_ref().map(Vec::len)
+                );
+                if let Ok(schedule) = found {
+                    assert!(instance.is_valid_schedule(&schedule));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_apart_against_brute_force() {
+        check_against_brute_force(1..40, |seed| {
+            let n_photos = 3 + seed as usize % 4;
+            let strict = random_graph(seed, n_photos, n_photos / 2);
+            let weak = random_graph(seed * 7 + 3, n_photos, n_photos / 2);
+            let apart = random_graph(seed * 11 + 1, n_photos, n_photos);
+            let mut builder = DependencyGraph::builder(n_photos)
+                .edges(strict.edges())
+                .weak_edges(weak.edges());
+            for (u, v) in apart.edges() {
+                builder = builder.apart(u, v);
+            }
+            let graph = builder.build().unwrap();
+            (1..4)
+                .map(|max_by_page| Instance::new(graph.clone(), max_by_page))
+                .collect()
+        });
+    }
+    #[test]
+    fn test_last_against_brute_force() {
+        use crate::solver::Method;
+        for seed in 1..40 {
+            let n_photos = 3 + seed as usize % 4;
+            let strict = random_graph(seed, n_photos, n_photos / 2);
+            let weak = random_graph(seed * 7 + 3, n_photos, n_photos / 2);
+            let last = random_graph(seed * 3 + 1, n_photos, 1);
+            let mut builder = DependencyGraph::builder(n_photos)
+                .edges(strict.edges())
+                .weak_edges(weak.edges());
+            for (u, v) in last.edges() {
+                builder = builder.last(u).last(v);
+            }
+            let graph = builder.build().unwrap();
+            // The solver rejects the last pages too small for their photos first.
+            for max_by_page in 1..4 {
+                let instance = Instance::new(graph.clone(), max_by_page);
+                let expected = brute_force(&instance);
+                let found = Solver::new(&instance).min_pages().ok();
+                assert_eq!(found, expected);
+                let reference = Solver::new(&instance).method(Method::Reference);
+                assert_eq!(reference.min_pages().ok(), expected);
+            }
+        }
+    }
+    #[test]
+    fn test_pins_against_brute_force() {
+        use crate::instance::Pin;
+        check_against_brute_force(1..60, |seed| {
+            let n_photos = 3 + seed as usize % 3;
+            let graph = random_graph(seed, n_photos, n_photos);
+            let pins = random_graph(seed * 5 + 2, n_photos, 3);
+            let mut instance = Instance::new(graph, 2);
+            for (i, (photo, page)) in pins.edges().enumerate() {
+                let pin = match (seed as usize + i) % 3 {
+                    0 => Pin::exactly(page as usize - 1),
+                    1 => Pin::release(page as usize),
+                    _ => Pin::deadline(page as usize - 1),
+                };
+                instance.pins.insert(photo, pin);
+            }
+            vec![instance]
+        });
+    }
+    #[test]
+    fn test_lags_against_brute_force() {
+        check_against_brute_force(1..50, |seed| {
+            let n_photos = 3 + seed as usize % 3;
+            let strict = random_graph(seed, n_photos, n_photos / 2);
+            let lagged = random_graph(seed * 3 + 1, n_photos, n_photos / 2);
+            let graph = DependencyGraph::builder(n_photos)
+                .edges(strict.edges())
+                .lagged_edges(lagged.edges().map(|(u, v)| (u, v, (u + v) as usize % 4)))
+                .build()
+                .unwrap();
+            (1..4)
+                .map(|max_by_page| Instance::new(graph.clone(), max_by_page))
+                .collect()
+        });
+    }
+    #[test]
+    fn test_chapters_against_brute_force() {
+        check_against_brute_force(1..60, |seed| {
+            let n_photos = 3 + seed as usize % 4;
+            let graph = random_graph(seed, n_photos, n_photos);
+            let mut instance = Instance::new(graph, 2);
+            // Three chapters, and some photos in none.
+            for photo in 1..=n_photos as u32 {
+                match (photo as u64 * seed) % 4 {
+                    0 => (),
+                    label => {
+                        instance.chapters.insert(photo, label as usize);
+                    }
+                }
+            }
+            vec![instance]
+        });
+    }
+    #[test]
+    fn test_spreads_against_brute_force() {
+        use crate::instance::Spreads;
+        check_against_brute_force(1..60, |seed| {
+            let n_photos = 3 + seed as usize % 2;
+            let graph = random_graph(seed, n_photos, n_photos);
+            let mut spreads = Spreads::new(seed % 2 == 0);
+            let pairs = random_graph(seed * 7 + 5, n_photos, 3);
+            for (i, (u, v)) in pairs.edges().enumerate() {
+                if (seed as usize + i).is_multiple_of(3) {
+                    spreads.apart.push((u, v));
+                } else {
+                    spreads.together.push(vec![u, v]);
+                }
+            }
+            (1..3)
+                .map(|max_by_page| {
+                    let mut instance = Instance::new(graph.clone(), max_by_page);
+                    instance.spreads = Some(spreads.clone());
+                    instance
+                })
+                .collect()
+        });
+    }
+    #[test]
+    fn test_schedules_and_counts_against_brute_force() {
+        use crate::instance::{Pin, Spreads};
+        for seed in 1..40 {
+            let n_photos = 3 + seed as usize % 3;
+            let strict = random_graph(seed, n_photos, n_photos / 2);
+            let weak = random_graph(seed * 7 + 3, n_photos, 2);
+            let graph = DependencyGraph::builder(n_photos)
+                .edges(strict.edges())
+                .weak_edges(weak.edges())
+                .build()
+                .unwrap();
+            let mut instance = Instance::with_capacities(graph, Capacities::new(vec![1], vec![2]));
+            match seed % 4 {
+                0 => {
+                    instance.pins.insert(1, Pin::release(2));
+                }
+                1 => {
+                    instance.chapters.insert(2, 0);
+                    instance.chapters.insert(3, 0);
+                }
+                2 => {
+                    let mut spreads = Spreads::new(true);
+                    spreads.together.push(vec![1, 3]);
+                    instance.spreads = Some(spreads);
+                }
+                _ => (),
+            }
+            let Some(n_pages) = BitmaskSolver::new(&instance).min_pages() else {
+                continue;
+            };
+            let mut schedules: Vec<_> =
+                BitmaskSolver::new(&instance).schedules().unwrap().collect();
+            schedules.sort();
+            let count = schedules.len();
+            schedules.dedup();
+            assert_eq!(schedules.len(), count);
+            let mut expected = brute_force_schedules(&instance, n_pages);
+            expected.sort();
+            assert_eq!(schedules, expected);
+            // Counting, with the optimal number of pages and one more.
+            let mut solver = BitmaskSolver::new(&instance);
+            assert_eq!(solver.count_schedules(n_pages), Count::from(count as u64));
+            let count = brute_force_schedules(&instance, n_pages + 1).len();
+            assert_eq!(
+                solver.count_schedules(n_pages + 1),
+                Count::from(count as u64)
+            );
+            assert_eq!(solver.count_schedules(n_pages - 1), Count::zero());
+        }
+    }
+    #[test]
+    fn test_sample() {
+        // Three photos, two by page: six optimal schedules, each drawn about 100 times.
+        let instance = Instance::new(DependencyGraph::new(vec![], 3), 2);
+        let mut solver = BitmaskSolver::new(&instance);
+        let mut random = Random::new(1);
+        let mut drawn: HashMap<Schedule, usize> = HashMap::new();
+        for _ in 0..600 {
+            let schedule = solver.sample(&mut random).unwrap();
+            assert!(instance.is_valid_schedule(&schedule));
+            *drawn.entry(schedule).or_insert(0) += 1;
+        }
+        assert_eq!(drawn.len(), 6);
+        assert!(drawn.values().all(|&n| n > 60));
+        let cycle = DependencyGraph::new(vec![(1, 2), (2, 1)], 2);
+        let instance = Instance::new(cycle, 2);
+        assert_eq!(BitmaskSolver::new(&instance).sample(&mut random), None);
+    }
+    #[test]
+    fn test_large_instance() {
+        // 32 photos: four chains of three plus a wide layer depending on them.
+        let mut edges = Vec::new();
+        for chain in 0..4 {
+            edges.push((3 * chain + 1, 3 * chain + 2));
+            edges.push((3 * chain + 2, 3 * chain + 3));
+            for v in 13..=32 {
+                if v % 4 == chain {
+                    edges.push((3 * chain + 3, v));
+                }
+            }
+        }
+        let instance = Instance::new(DependencyGraph::new(edges, 32), 3);
+        let mut solver = BitmaskSolver::new(&instance);
+        assert_eq!(solver.min_pages(), Some(11));
+        let schedule = solver.schedule().unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+    }
+}
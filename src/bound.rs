@@ -0,0 +1,106 @@
+//! Lower bounds on the number of pages, to prune the exact search
+//! and to measure how far a heuristic schedule may be from the optimum.
+
+use crate::graph::DependencyGraph;
+use crate::instance::Instance;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// Return a lower bound on the minimum number of pages of an acyclic instance.
+pub(crate) fn min_pages_bound(instance: &Instance) -> usize {
+    let tails = tails(&instance.graph);
+    lower_bound(&instance.graph, instance, 0, &[], &tails)
+}
+
+/// Return for each photo of `graph` the fewest pages from its own to the last one,
+/// following the edges and their lags.
+pub(crate) fn tails(graph: &DependencyGraph) -> BTreeMap<u32, usize> {
+    fn visit(graph: &DependencyGraph, photo: u32, tails: &mut BTreeMap<u32, usize>) -> usize {
+        if let Some(&tail) = tails.get(&photo) {
+            return tail;
+        }
+        let tail = graph.adj_list[&photo]
+            .iter()
+            .map(|&v| graph.lag(photo, v) + visit(graph, v, tails))
+            .max()
+            .unwrap_or(1);
+        tails.insert(photo, tail);
+        tail
+    }
+    let mut tails = BTreeMap::new();
+    for &photo in graph.adj_list.keys() {
+        visit(graph, photo, &mut tails);
+    }
+    tails
+}
+
+/// Return a lower bound on the number of pages needed for the photos of `graph`
+/// from page `page` on, the photos of `waits` being held back by lags.
+pub(crate) fn lower_bound(
+    graph: &DependencyGraph,
+    instance: &Instance,
+    page: usize,
+    waits: &[(u32, usize)],
+    tails: &BTreeMap<u32, usize>,
+) -> usize {
+    // A photo comes after its predecessors, so before its successors in this order.
+    let mut photos: Vec<u32> = graph.adj_list.keys().copied().collect();
+    photos.sort_by_key(|photo| Reverse(tails[photo]));
+    // Longest chain: the first page a photo can go on (pins counting pages from 1),
+    // then the pages of its tail.
+    let mut earliest: BTreeMap<u32, usize> = photos
+        .iter()
+        .map(|&photo| {
+            (
+                photo,
+                (instance.pin(photo).release - 1).saturating_sub(page),
+            )
+        })
+        .collect();
+    for &(photo, wait) in waits {
+        if let Some(first) = earliest.get_mut(&photo) {
+            *first = (*first).max(wait);
+        }
+    }
+    let mut bound = 0;
+    for &u in &photos {
+        let first = earliest[&u];
+        bound = bound.max(first + tails[&u]);
+        for &v in &graph.adj_list[&u] {
+            let next = earliest.get_mut(&v).unwrap();
+            *next = (*next).max(first + graph.lag(u, v));
+        }
+    }
+    // Hu's bound: the photos with a tail of `d` pages or more all go before the last
+    // `d - 1` pages. With `d = 1`, all the photos need room on the pages.
+    let mut size = 0;
+    for (i, &photo) in photos.iter().enumerate() {
+        size += instance.size(photo);
+        let tail = tails[&photo];
+        if photos.get(i + 1).is_none_or(|next| tails[next] < tail) {
+            bound = bound.max(instance.capacities.pages_for(page, size) + tail - 1);
+        }
+    }
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_bound() {
+        // A chain of three photos and four others, three by page.
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3)], 7);
+        let instance = Instance::new(g.clone(), 3);
+        let tails = tails(&g);
+        assert_eq!((tails[&1], tails[&3], tails[&4]), (3, 1, 1));
+        assert_eq!(lower_bound(&g, &instance, 0, &[], &tails), 3);
+        // Photo 1 waits for two more pages.
+        assert_eq!(lower_bound(&g, &instance, 0, &[(1, 2)], &tails), 5);
+        // Four photos before the last page, which only one photo needs.
+        let g = DependencyGraph::new((1..5).map(|i| (i, 5)).collect(), 5);
+        let instance = Instance::new(g.clone(), 3);
+        assert_eq!(min_pages_bound(&instance), 3);
+    }
+}
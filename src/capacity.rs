@@ -0,0 +1,285 @@
+/// Number of photos each page can hold: the pages start with the capacities
+/// of `first`, then the capacities of `pattern` repeat until the end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capacities {
+    first: Vec<usize>,
+    pattern: Vec<usize>,
+}
+
+impl Capacities {
+    /// Every page holds `max_by_page` photos.
+    pub fn uniform(max_by_page: usize) -> Self {
+        Self::new(Vec::new(), vec![max_by_page])
+    }
+    /// Capacities given page by page for the first pages, then following `pattern`.
+    ///
+    /// Panics if `pattern` is empty.
+    pub fn new(first: Vec<usize>, pattern: Vec<usize>) -> Self {
+        assert!(!pattern.is_empty(), "the repeated pattern cannot be empty");
+        Self { first, pattern }
+    }
+    /// Return the capacity of page `page`, numbered from 0.
+    pub fn of_page(&self, page: usize) -> usize {
+        match self.first.get(page) {
+            Some(&capacity) => capacity,
+            None => self.pattern[(page - self.first.len()) % self.pattern.len()],
+        }
+    }
+    /// Iterate over the capacities from page `page` on.
+    pub fn from_page(&self, page: usize) -> impl Iterator<Item = usize> + '_ {
+        (page..).map(move |page| self.of_page(page))
+    }
+    /// Return the same number as `page` for all the pages followed by the same capacities,
+    /// so that it can be used as a key for the rest of the book.
+    pub(crate) fn canonical_page(&self, page: usize) -> usize {
+        match page.checked_sub(self.first.len()) {
+            Some(offset) => self.first.len() + offset % self.pattern.len(),
+            None => page,
+        }
+    }
+    /// Return a number of pages after which any page has been followed
+    /// by every capacity of the repeated pattern.
+    pub(crate) fn period(&self) -> usize {
+        self.first.len() + self.pattern.len()
+    }
+    /// Return the number of pages from page `page` on needed to have room for `n_photos`.
+    pub fn pages_for(&self, page: usize, n_photos: usize) -> usize {
+        let (mut n_pages, mut room) = (0, 0);
+        for capacity in self.from_page(page) {
+            if room >= n_photos {
+                break;
+            }
+            room += capacity;
+            n_pages += 1;
+        }
+        n_pages
+    }
+    /// Return the smallest capacity of a page.
+    pub fn min(&self) -> usize {
+        self.first
+            .iter()
+            .chain(&self.pattern)
+            .copied()
+            .min()
+            .unwrap()
+    }
+    /// Return the largest capacity of a page.
+    pub fn max(&self) -> usize {
+        self.first
+            .iter()
+            .chain(&self.pattern)
+            .copied()
+            .max()
+            .unwrap()
+    }
+    /// Return the largest capacity of the repeated pages.
+    pub fn max_repeated(&self) -> usize {
+        self.pattern.iter().copied().max().unwrap()
+    }
+    /// Return the capacities where no page holds more than `max_by_page` photos.
+    pub fn capped(&self, max_by_page: usize) -> Self {
+        let cap = |capacities: &[usize]| {
+            capacities
+                .iter()
+                .map(|&capacity| capacity.min(max_by_page))
+                .collect()
+        };
+        Self::new(cap(&self.first), cap(&self.pattern))
+    }
+    /// Return the capacities where the first `n_full` pages hold at most `max_by_page` photos
+    /// and the next ones at most `max_by_page - 1`.
+    pub(crate) fn fuller_first(&self, n_full: usize, max_by_page: usize) -> Self {
+        // Cover the first `n_full` pages with whole repetitions of the pattern,
+        // so that the pages afterwards stay aligned with it.
+        let repeated = n_full.saturating_sub(self.first.len());
+        let n_first = self.first.len() + repeated.div_ceil(self.pattern.len()) * self.pattern.len();
+        let first = (0..n_first)
+            .map(|page| {
+                let max = if page < n_full {
+                    max_by_page
+                } else {
+                    max_by_page - 1
+                };
+                self.of_page(page).min(max)
+            })
+            .collect();
+        let pattern = self
+            .pattern
+            .iter()
+            .map(|&capacity| capacity.min(max_by_page - 1));
+        Self::new(first, pattern.collect())
+    }
+    /// Return `Some(m)` if every page holds `m` photos.
+    pub fn as_uniform(&self) -> Option<usize> {
+        let m = self.pattern[0];
+        if self
+            .first
+            .iter()
+            .chain(&self.pattern)
+            .all(|&capacity| capacity == m)
+        {
+            Some(m)
+        } else {
+            None
+        }
+    }
+}
+
+/// Photos that can go on the next page, but only all together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Unit {
+    pub photos: Vec<u32>,
+    pub size: usize,
+    /// Indices of the earlier units that must be on the page for this one to be.
+    pub requires: Vec<usize>,
+    /// Indices of the units that cannot be on the same page as this one.
+    pub conflicts: Vec<usize>,
+}
+
+impl Unit {
+    /// A single photo of size `size`.
+    #[cfg(test)]
+    pub fn photo(photo: u32, size: usize) -> Self {
+        Self {
+            photos: vec![photo],
+            size,
+            requires: Vec::new(),
+            conflicts: Vec::new(),
+        }
+    }
+    /// Return if the unit can be added to the `chosen` units.
+    fn allowed(&self, chosen: &[bool]) -> bool {
+        self.requires.iter().all(|&j| chosen[j]) && self.conflicts.iter().all(|&j| !chosen[j])
+    }
+}
+
+/// Return the sets of `units` that fit in a page of capacity `capacity` and to which
+/// no other unit can be added, each page sorted and in lexicographic order of the units.
+pub(crate) fn maximal_pages(units: &[Unit], capacity: usize) -> Vec<Vec<u32>> {
+    first_pages(units, capacity, true, usize::MAX)
+}
+
+/// Return all the sets of `units` that fit in a page of capacity `capacity`,
+/// including the empty one, in the same order as `maximal_pages`.
+pub(crate) fn all_pages(units: &[Unit], capacity: usize) -> Vec<Vec<u32>> {
+    first_pages(units, capacity, false, usize::MAX)
+}
+
+/// Return the first `limit` pages of `maximal_pages`, or of `all_pages` if not `only_maximal`.
+pub(crate) fn first_pages(
+    units: &[Unit],
+    capacity: usize,
+    only_maximal: bool,
+    limit: usize,
+) -> Vec<Vec<u32>> {
+    fn fill(
+        units: &[Unit],
+        i: usize,
+        room: usize,
+        only_maximal: bool,
+        limit: usize,
+        chosen: &mut Vec<bool>,
+        result: &mut Vec<Vec<u32>>,
+    ) {
+        if result.len() >= limit {
+            return;
+        }
+        match units.get(i) {
+            None => {
+                // Adding a unit whose requirements are left out means adding them first,
+                // so it is enough to try the units allowed alone.
+                let maximal = units
+                    .iter()
+                    .enumerate()
+                    .all(|(j, unit)| chosen[j] || unit.size > room || !unit.allowed(chosen));
+                if maximal || !only_maximal {
+                    let mut page: Vec<u32> = (0..units.len())
+                        .filter(|&j| chosen[j])
+                        .flat_map(|j| units[j].photos.iter().copied())
+                        .collect();
+                    page.sort_unstable();
+                    result.push(page)
+                }
+            }
+            Some(unit) => {
+                if unit.size <= room && unit.allowed(chosen) {
+                    chosen[i] = true;
+                    let room = room - unit.size;
+                    fill(units, i + 1, room, only_maximal, limit, chosen, result);
+                    chosen[i] = false;
+                }
+                fill(units, i + 1, room, only_maximal, limit, chosen, result);
+            }
+        }
+    }
+    let mut result = Vec::new();
+    let mut chosen = vec![false; units.len()];
+    fill(
+        units,
+        0,
+        capacity,
+        only_maximal,
+        limit,
+        &mut chosen,
+        &mut result,
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacities() {
+        let capacities = Capacities::new(vec![1, 2], vec![4, 3]);
+        let first: Vec<_> = capacities.from_page(0).take(7).collect();
+        assert_eq!(first, vec![1, 2, 4, 3, 4, 3, 4]);
+        assert_eq!(capacities.canonical_page(1), 1);
+        assert_eq!(capacities.canonical_page(6), 2);
+        assert_eq!(capacities.pages_for(0, 0), 0);
+        assert_eq!(capacities.pages_for(0, 3), 2);
+        assert_eq!(capacities.pages_for(0, 4), 3);
+        assert_eq!(capacities.pages_for(2, 8), 3);
+        assert_eq!((capacities.min(), capacities.max()), (1, 4));
+        assert_eq!(capacities.as_uniform(), None);
+        assert_eq!(Capacities::uniform(3).as_uniform(), Some(3));
+        assert_eq!(Capacities::new(vec![2], vec![2, 2]).as_uniform(), Some(2));
+        assert_eq!(
+            capacities.capped(2),
+            Capacities::new(vec![1, 2], vec![2, 2])
+        );
+        let fuller_first = capacities.fuller_first(3, 3);
+        let first: Vec<_> = fuller_first.from_page(0).take(7).collect();
+        assert_eq!(first, vec![1, 2, 3, 2, 2, 2, 2]);
+    }
+    #[test]
+    fn test_maximal_pages() {
+        let units = |items: &[(u32, usize)]| -> Vec<Unit> {
+            items
+                .iter()
+                .map(|&(photo, size)| Unit::photo(photo, size))
+                .collect()
+        };
+        let pages = vec![vec![1, 2], vec![1, 3], vec![2, 3]];
+        assert_eq!(maximal_pages(&units(&[(1, 1), (2, 1), (3, 1)]), 2), pages);
+        let items = units(&[(1, 2), (2, 1), (3, 3), (4, 1)]);
+        let pages = vec![vec![1, 2], vec![1, 4], vec![2, 4], vec![3]];
+        assert_eq!(maximal_pages(&items, 3), pages);
+        assert_eq!(maximal_pages(&units(&[(1, 3)]), 2), vec![Vec::<u32>::new()]);
+        // Photos 1 and 2 go together, and photo 3 needs them on the same page.
+        let mut items = units(&[(1, 2), (3, 1), (4, 1)]);
+        items[0].photos.push(2);
+        items[1].requires.push(0);
+        assert_eq!(maximal_pages(&items, 2), vec![vec![1, 2], vec![4]]);
+        let pages = vec![vec![1, 2, 3], vec![1, 2, 4]];
+        assert_eq!(maximal_pages(&items, 3), pages);
+        // Photos 3 and 4 cannot share a page.
+        items[1].conflicts.push(2);
+        items[2].conflicts.push(1);
+        assert_eq!(maximal_pages(&items, 4), pages);
+        let pages = vec![vec![1, 2, 3], vec![1, 2, 4], vec![1, 2], vec![4], vec![]];
+        assert_eq!(all_pages(&items, 3), pages);
+        assert_eq!(first_pages(&items, 3, false, 2), pages[..2]);
+    }
+}
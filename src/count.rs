@@ -0,0 +1,182 @@
+//! Exact numbers of schedules, which quickly exceed any machine integer.
+//!
+//! Counting only adds numbers up (and sampling subtracts them), so a natural number
+//! is stored as its digits in base 10^9, which also makes printing it in decimal
+//! straightforward.
+
+use crate::random::Random;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{AddAssign, SubAssign};
+
+/// Base of the digits of a `Count`.
+const BASE: u32 = 1_000_000_000;
+
+/// A natural number of any size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Count {
+    /// Digits in base `BASE`, least significant first, without leading zeros.
+    digits: Vec<u32>,
+}
+
+impl Count {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+    pub fn one() -> Self {
+        Self::from(1)
+    }
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+    /// Return the number if it fits in a `u64`.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.digits.iter().rev().try_fold(0_u64, |n, &digit| {
+            n.checked_mul(BASE as u64)?.checked_add(digit as u64)
+        })
+    }
+    /// Draw a number in `0..self` uniformly, `self` being positive.
+    pub(crate) fn random_below(&self, random: &mut Random) -> Count {
+        let (&top, rest) = self.digits.split_last().unwrap();
+        loop {
+            // Draw the digits below the top one freely, and retry when above `self`.
+            let mut digits: Vec<u32> = rest
+                .iter()
+                .map(|_| random.below(BASE as u64) as u32)
+                .collect();
+            digits.push(random.below(top as u64 + 1) as u32);
+            while digits.last() == Some(&0) {
+                digits.pop();
+            }
+            let n = Count { digits };
+            if n < *self {
+                return n;
+            }
+        }
+    }
+}
+
+impl From<u64> for Count {
+    fn from(mut n: u64) -> Self {
+        let mut digits = Vec::new();
+        while n > 0 {
+            digits.push((n % BASE as u64) as u32);
+            n /= BASE as u64;
+        }
+        Self { digits }
+    }
+}
+
+impl AddAssign<&Count> for Count {
+    fn add_assign(&mut self, other: &Count) {
+        if self.digits.len() < other.digits.len() {
+            self.digits.resize(other.digits.len(), 0);
+        }
+        let mut carry = 0;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            let sum = *digit + other.digits.get(i).copied().unwrap_or(0) + carry;
+            *digit = sum % BASE;
+            carry = sum / BASE;
+            if carry == 0 && i >= other.digits.len() {
+                return;
+            }
+        }
+        if carry > 0 {
+            self.digits.push(carry);
+        }
+    }
+}
+
+impl SubAssign<&Count> for Count {
+    /// Subtract a number no larger than `self`.
+    fn sub_assign(&mut self, other: &Count) {
+        let mut borrow = 0;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            let subtracted = other.digits.get(i).copied().unwrap_or(0) + borrow;
+            if *digit >= subtracted {
+                *digit -= subtracted;
+                borrow = 0;
+            } else {
+                *digit = *digit + BASE - subtracted;
+                borrow = 1;
+            }
+        }
+        assert_eq!(borrow, 0, "subtracting a larger count");
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+}
+
+impl Ord for Count {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for Count {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.digits.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for digit in rest.iter().rev() {
+                    write!(f, "{:09}", digit)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count() {
+        let mut n = Count::from(999_999_999_999_999_999);
+        n += &Count::one();
+        assert_eq!(n.to_string(), "1000000000000000000");
+        assert_eq!(n.to_u64(), Some(1_000_000_000_000_000_000));
+        let mut big = Count::from(u64::MAX);
+        big += &Count::from(u64::MAX);
+        assert_eq!(big.to_string(), "36893488147419103230");
+        assert_eq!(big.to_u64(), None);
+        assert!(big > n && n > Count::zero());
+        assert_eq!(Count::zero().to_string(), "0");
+        assert!(Count::zero().is_zero() && !n.is_zero());
+        let mut sum = Count::zero();
+        sum += &Count::from(1_000_000_005);
+        assert_eq!(sum, Count::from(1_000_000_005));
+        big -= &Count::from(u64::MAX);
+        assert_eq!(big, Count::from(u64::MAX));
+        sum -= &Count::from(6);
+        assert_eq!(sum, Count::from(999_999_999));
+        sum -= &Count::from(999_999_999);
+        assert!(sum.is_zero());
+    }
+    #[test]
+    fn test_random_below() {
+        let mut random = Random::new(7);
+        let bound = Count::from(3_000_000_001);
+        // Each of the first three billions is drawn.
+        let mut seen = [false; 4];
+        for _ in 0..100 {
+            let n = bound.random_below(&mut random);
+            assert!(n < bound);
+            seen[(n.to_u64().unwrap() / 1_000_000_000) as usize] = true;
+        }
+        assert_eq!(seen[..3], [true; 3]);
+        assert_eq!(Count::one().random_below(&mut random), Count::zero());
+    }
+}
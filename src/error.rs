@@ -0,0 +1,382 @@
+use crate::instance::Pin;
+use itertools::Itertools;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Invalid edge given to a `DependencyGraphBuilder`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// An edge mentions a photo outside of `1..=n_photos`.
+    PhotoOutOfRange { photo: u32, n_photos: usize },
+    /// An edge from a photo to itself.
+    SelfLoop { photo: u32 },
+    /// A photo apart from itself.
+    SelfApart { photo: u32 },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::PhotoOutOfRange { photo, n_photos } => {
+                write!(f, "photo {} is not in 1..={}", photo, n_photos)
+            }
+            GraphError::SelfLoop { photo } => {
+                write!(f, "photo {} cannot come before itself", photo)
+            }
+            GraphError::SelfApart { photo } => {
+                write!(f, "photo {} cannot be apart from itself", photo)
+            }
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+/// Position of a token in an input file (line and column start at 1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Name of the file, when reading from a file.
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub(crate) fn new(line: usize, column: usize) -> Self {
+        Self {
+            file: None,
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Error while reading an input file.
+#[derive(Debug)]
+pub enum ParseError {
+    Io {
+        file: Option<String>,
+        error: io::Error,
+    },
+    /// The input is empty.
+    MissingHeader { location: Location },
+    /// The header does not have exactly the three numbers `n m k`.
+    HeaderArity { location: Location, found: usize },
+    /// The header declares more photos than an input may have.
+    TooManyPhotos {
+        location: Location,
+        n_photos: usize,
+        max: usize,
+    },
+    /// A token is not a non-negative integer.
+    InvalidNumber { location: Location, token: String },
+    /// An edge line with a single photo.
+    MissingPhoto { location: Location },
+    /// A line with too many numbers, located at the first extra one.
+    ExtraNumber {
+        location: Location,
+        token: String,
+        expected: String,
+    },
+    /// An edge mentions a photo outside of `1..=n_photos`.
+    PhotoOutOfRange {
+        location: Location,
+        photo: u32,
+        n_photos: usize,
+    },
+    /// An edge from a photo to itself.
+    SelfLoop { location: Location, photo: u32 },
+    /// A photo apart from itself.
+    SelfApart { location: Location, photo: u32 },
+    /// A photo listed in two chapters.
+    TwoChapters { location: Location, photo: u32 },
+    /// A page numbered 0.
+    PageZero { location: Location },
+    /// A photo taking no slot.
+    ZeroSize { location: Location, photo: u32 },
+    /// A line starts with a word that is not a directive.
+    UnknownDirective { location: Location, token: String },
+    /// A directive given twice.
+    DuplicateDirective {
+        location: Location,
+        directive: String,
+    },
+    /// A directive without its arguments, located at the end of the line.
+    MissingArgument {
+        location: Location,
+        directive: String,
+    },
+    /// The number of edges is not the `k` of the header, located at the first
+    /// extra edge or at `k` if edges are missing.
+    EdgeCount {
+        location: Location,
+        declared: usize,
+        found: usize,
+    },
+}
+
+impl ParseError {
+    /// Return where the error occured, if it is in the content of the input.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ParseError::Io { .. } => None,
+            ParseError::MissingHeader { location }
+            | ParseError::HeaderArity { location, .. }
+            | ParseError::TooManyPhotos { location, .. }
+            | ParseError::InvalidNumber { location, .. }
+            | ParseError::MissingPhoto { location }
+            | ParseError::ExtraNumber { location, .. }
+            | ParseError::PhotoOutOfRange { location, .. }
+            | ParseError::SelfLoop { location, .. }
+            | ParseError::SelfApart { location, .. }
+            | ParseError::TwoChapters { location, .. }
+            | ParseError::ZeroSize { location, .. }
+            | ParseError::PageZero { location }
+            | ParseError::UnknownDirective { location, .. }
+            | ParseError::DuplicateDirective { location, .. }
+            | ParseError::MissingArgument { location, .. }
+            | ParseError::EdgeCount { location, .. } => Some(location),
+        }
+    }
+    /// Record the name of the file the error comes from.
+    pub(crate) fn in_file(mut self, filename: &str) -> Self {
+        let file = match &mut self {
+            ParseError::Io { file, .. } => file,
+            ParseError::MissingHeader { location }
+            | ParseError::HeaderArity { location, .. }
+            | ParseError::TooManyPhotos { location, .. }
+            | ParseError::InvalidNumber { location, .. }
+            | ParseError::MissingPhoto { location }
+            | ParseError::ExtraNumber { location, .. }
+            | ParseError::PhotoOutOfRange { location, .. }
+            | ParseError::SelfLoop { location, .. }
+            | ParseError::SelfApart { location, .. }
+            | ParseError::TwoChapters { location, .. }
+            | ParseError::ZeroSize { location, .. }
+            | ParseError::PageZero { location }
+            | ParseError::UnknownDirective { location, .. }
+            | ParseError::DuplicateDirective { location, .. }
+            | ParseError::MissingArgument { location, .. }
+            | ParseError::EdgeCount { location, .. } => &mut location.file,
+        };
+        *file = Some(filename.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}: ", location)?;
+        }
+        match self {
+            ParseError::Io { file, error } => match file {
+                Some(file) => write!(f, "{}: {}", file, error),
+                None => write!(f, "{}", error),
+            },
+            ParseError::MissingHeader { .. } => write!(f, "missing header line `n m k`"),
+            ParseError::HeaderArity { found, .. } => write!(
+                f,
+                "the header should contain 3 numbers `n m k`, found {}",
+                found
+            ),
+            ParseError::TooManyPhotos { n_photos, max, .. } => {
+                write!(
+                    f,
+                    "{} photos declared, at most {} are supported",
+                    n_photos, max
+                )
+            }
+            ParseError::InvalidNumber { token, .. } => {
+                write!(f, "`{}` is not a valid number", token)
+            }
+            ParseError::MissingPhoto { .. } => write!(f, "an edge needs two photos"),
+            ParseError::ExtraNumber {
+                token, expected, ..
+            } => write!(f, "unexpected `{}`, {}", token, expected),
+            ParseError::PhotoOutOfRange {
+                photo, n_photos, ..
+            } => write!(f, "photo {} is not in 1..={}", photo, n_photos),
+            ParseError::SelfLoop { photo, .. } => {
+                write!(f, "photo {} cannot come before itself", photo)
+            }
+            ParseError::SelfApart { photo, .. } => {
+                write!(f, "photo {} cannot be apart from itself", photo)
+            }
+            ParseError::TwoChapters { photo, .. } => {
+                write!(f, "photo {} is already in another chapter", photo)
+            }
+            ParseError::PageZero { .. } => write!(f, "pages are numbered from 1"),
+            ParseError::ZeroSize { photo, .. } => {
+                write!(f, "photo {} cannot have size 0", photo)
+            }
+            ParseError::UnknownDirective { token, .. } => {
+                write!(f, "unknown directive `{}`", token)
+            }
+            ParseError::DuplicateDirective { directive, .. } => {
+                write!(f, "`{}` is given twice", directive)
+            }
+            ParseError::MissingArgument { directive, .. } => {
+                write!(f, "`{}` needs more arguments", directive)
+            }
+            ParseError::EdgeCount {
+                declared, found, ..
+            } => write!(
+                f,
+                "the header declares {} edges but {} were found",
+                declared, found
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io { file: None, error }
+    }
+}
+
+/// Reason why an instance cannot be solved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    /// The constraints contain a directed cycle, given as its photos in order.
+    Cyclic { cycle: Vec<u32> },
+    /// A page cannot hold any photo.
+    ZeroCapacity,
+    /// The number of pages is asked to be a multiple of 0.
+    ZeroPageMultiple,
+    /// The number of sizes is not the number of photos.
+    SizeCount { n_photos: usize, n_sizes: usize },
+    /// A photo has size 0 or does not fit on the pages repeated until the end.
+    InvalidSize { photo: u32, size: usize },
+    /// Photos that must share a page, and a chain of constraints from one of them to
+    /// one of them that puts a page break in between.
+    SeparatedGroup { photos: Vec<u32>, chain: Vec<u32> },
+    /// Photos that must be apart are among photos that must share a page.
+    ApartTogether { pair: (u32, u32), photos: Vec<u32> },
+    /// Photos that must share a page do not fit on the pages repeated until the end.
+    GroupTooLarge {
+        photos: Vec<u32>,
+        size: usize,
+        capacity: usize,
+    },
+    /// A pin allows no page, or is given for a photo outside of `1..=n_photos`.
+    InvalidPin { photo: u32, pin: Pin },
+    /// No schedule places every pinned photo on a page it allows.
+    PinsUnsatisfiable,
+    /// A chapter is given for a photo outside of `1..=n_photos`.
+    InvalidChapter { photo: u32, n_photos: usize },
+    /// No schedule keeps the photos of other chapters out of the pages of each chapter.
+    ChaptersUnsatisfiable,
+    /// A spread constraint is given for a photo outside of `1..=n_photos`.
+    InvalidSpread { photo: u32, n_photos: usize },
+    /// No schedule puts the photos of each group on the same spread and the apart ones
+    /// on different spreads.
+    SpreadsUnsatisfiable,
+    /// The chosen method cannot handle that many photos.
+    TooManyPhotos { n_photos: usize, max: usize },
+    /// The heuristic found no valid schedule, which the exact methods may still find.
+    HeuristicFailed,
+    /// The search reached its limits before finding any schedule, nor did the heuristic.
+    Stopped,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::Cyclic { cycle } => write!(
+                f,
+                "the constraints contain the cycle {} -> {}",
+                cycle.iter().join(" -> "),
+                cycle[0]
+            ),
+            SolveError::ZeroCapacity => write!(f, "pages must hold at least one photo"),
+            SolveError::ZeroPageMultiple => write!(f, "the page multiple must be at least 1"),
+            SolveError::SizeCount { n_photos, n_sizes } => {
+                write!(f, "{} sizes given for {} photos", n_sizes, n_photos)
+            }
+            SolveError::InvalidSize { photo, size } => {
+                write!(f, "photo {} cannot have size {}", photo, size)
+            }
+            SolveError::SeparatedGroup { photos, chain } => write!(
+                f,
+                "photos {} must share a page but the constraints {} separate them",
+                photos.iter().join(", "),
+                chain.iter().join(" -> ")
+            ),
+            SolveError::ApartTogether { pair, photos } => write!(
+                f,
+                "photos {} and {} must be apart but photos {} must share a page",
+                pair.0,
+                pair.1,
+                photos.iter().join(", ")
+            ),
+            SolveError::GroupTooLarge {
+                photos,
+                size,
+                capacity,
+            } => write!(
+                f,
+                "photos {} must share a page but take {} slots, and the last pages hold at most {}",
+                photos.iter().join(", "),
+                size,
+                capacity
+            ),
+            SolveError::InvalidPin { photo, pin } => {
+                write!(f, "photo {} cannot be pinned to {}", photo, pin)
+            }
+            SolveError::PinsUnsatisfiable => {
+                write!(f, "the photos cannot all be placed on their pinned pages")
+            }
+            SolveError::InvalidChapter { photo, n_photos } => write!(
+                f,
+                "photo {} cannot have a chapter, not being in 1..={}",
+                photo, n_photos
+            ),
+            SolveError::ChaptersUnsatisfiable => write!(
+                f,
+                "the chapters cannot each be placed without another one in between"
+            ),
+            SolveError::InvalidSpread { photo, n_photos } => write!(
+                f,
+                "photo {} cannot have a spread constraint, not being in 1..={}",
+                photo, n_photos
+            ),
+            SolveError::SpreadsUnsatisfiable => write!(
+                f,
+                "the photos cannot be on the same or different spreads as required"
+            ),
+            SolveError::TooManyPhotos { n_photos, max } => write!(
+                f,
+                "{} photos is more than the solver can handle ({})",
+                n_photos, max
+            ),
+            SolveError::HeuristicFailed => write!(
+                f,
+                "the heuristic found no schedule meeting the pins, chapters and spreads"
+            ),
+            SolveError::Stopped => write!(f, "the search stopped before finding any schedule"),
+        }
+    }
+}
+
+impl Error for SolveError {}
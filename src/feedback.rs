@@ -0,0 +1,368 @@
+//! Feedback arc sets: edges whose removal makes an impossible instance feasible.
+//!
+//! Removing a set of edges makes the graph acyclic exactly when the remaining edges
+//! are compatible with some order of the photos, so we look for the order with the
+//! fewest backward edges. This is done independently in each strongly connected
+//! component: exactly by a dynamic program on subsets for small components, and
+//! with the greedy heuristic of Eades, Lin and Smyth for the larger ones.
+//!
+//! Weak edges are never dropped. The photos of a cycle of weak edges are contracted
+//! into one vertex first, as they share a page, and the strict edges between them
+//! always have to be dropped.
+
+use crate::graph::DependencyGraph;
+use std::collections::BTreeMap;
+
+/// Largest strongly connected component solved exactly.
+pub const MAX_EXACT_COMPONENT: usize = 16;
+
+/// A set of edges whose removal leaves the graph acyclic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedbackArcSet {
+    /// The edges to remove, a repeated edge appearing as many times as it is repeated.
+    pub edges: Vec<(u32, u32)>,
+    /// Whether the set is known to be of minimum size.
+    pub exact: bool,
+}
+
+impl DependencyGraph {
+    /// Compute a small set of strict edges whose removal makes the graph acyclic
+    /// (of minimum size when every cycle lies in a small enough component).
+    pub fn feedback_arc_set(&self) -> FeedbackArcSet {
+        let mut result = FeedbackArcSet {
+            edges: Vec::new(),
+            exact: true,
+        };
+        let (contracted, representative) = self.with_end().contract_weak_cycles();
+        for component in contracted.strongly_connected_components() {
+            if component.len() == 1 {
+                // Self-loops are rejected when building the graph.
+                continue;
+            }
+            let order = if component.len() <= MAX_EXACT_COMPONENT {
+                contracted.exact_order(&component)
+            } else {
+                result.exact = false;
+                contracted.greedy_order(&component)
+            };
+            let position: BTreeMap<u32, usize> =
+                order.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+            for (u, v) in self.edges() {
+                let (u_rep, v_rep) = (representative[&u], representative[&v]);
+                if let (Some(i), Some(j)) = (position.get(&u_rep), position.get(&v_rep)) {
+                    if i > j {
+                        result.edges.push((u, v));
+                    }
+                }
+            }
+        }
+        for (u, v) in self.edges() {
+            if representative[&u] == representative[&v] {
+                result.edges.push((u, v));
+            }
+        }
+        result.edges.sort_unstable();
+        result
+    }
+    /// Contract each cycle of weak edges into its smallest photo, and return the
+    /// contracted graph with the photo standing for each photo.
+    fn contract_weak_cycles(&self) -> (DependencyGraph, BTreeMap<u32, u32>) {
+        let mut representative = BTreeMap::new();
+        for component in strongly_connected_components(&self.weak_adj_list) {
+            for &v in &component {
+                representative.insert(v, component[0]);
+            }
+        }
+        let contract = |adj_list: &BTreeMap<u32, Vec<u32>>| {
+            let mut result: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+            for (u, neighbourhood) in adj_list {
+                let u = representative[u];
+                let targets = result.entry(u).or_default();
+                for v in neighbourhood {
+                    if representative[v] != u {
+                        targets.push(representative[v]);
+                    }
+                }
+            }
+            result
+        };
+        let contracted = DependencyGraph {
+            adj_list: contract(&self.adj_list),
+            weak_adj_list: contract(&self.weak_adj_list),
+            lags: BTreeMap::new(),
+            apart: Vec::new(),
+            last: Vec::new(),
+        };
+        (contracted, representative)
+    }
+    /// Return a copy of the graph without the given edges
+    /// (each listed edge removes one occurrence).
+    pub fn without_edges(&self, edges: &[(u32, u32)]) -> DependencyGraph {
+        let mut graph = self.clone();
+        for (u, v) in edges {
+            let neighbourhood = graph.adj_list.get_mut(u).unwrap();
+            if let Some(i) = neighbourhood.iter().position(|w| w == v) {
+                neighbourhood.remove(i);
+            }
+            if !neighbourhood.contains(v) {
+                graph.lags.remove(&(*u, *v));
+            }
+        }
+        graph
+    }
+    /// Return the strongly connected components, for the edges of both kinds.
+    pub(crate) fn strongly_connected_components(&self) -> Vec<Vec<u32>> {
+        let mut adj_list = self.adj_list.clone();
+        for (u, neighbourhood) in &self.weak_adj_list {
+            adj_list.get_mut(u).unwrap().extend(neighbourhood);
+        }
+        strongly_connected_components(&adj_list)
+    }
+    /// Order the vertices of a component with the fewest backward edges and no backward
+    /// weak edge, by dynamic programming on the set of vertices put first.
+    fn exact_order(&self, component: &[u32]) -> Vec<u32> {
+        let k = component.len();
+        // backward[i][j]: number of edges from component[i] to component[j]
+        let mut backward = vec![vec![0; k]; k];
+        for (u, v) in self.edges() {
+            if let (Ok(i), Ok(j)) = (component.binary_search(&u), component.binary_search(&v)) {
+                backward[i][j] += 1;
+            }
+        }
+        // weak_predecessors[i]: set of the vertices with a weak edge to component[i]
+        let mut weak_predecessors = vec![0usize; k];
+        for (u, v) in self.weak_edges() {
+            if let (Ok(i), Ok(j)) = (component.binary_search(&u), component.binary_search(&v)) {
+                weak_predecessors[j] |= 1 << i;
+            }
+        }
+        // cost[set]: fewest backward edges ordering `set` first, reached by adding `last[set]`
+        let mut cost = vec![usize::MAX; 1 << k];
+        let mut last = vec![0; 1 << k];
+        cost[0] = 0;
+        for set in 0..(1usize << k) {
+            if cost[set] == usize::MAX {
+                continue;
+            }
+            for (i, row) in backward.iter().enumerate() {
+                if set & (1 << i) != 0 || weak_predecessors[i] & !set != 0 {
+                    continue;
+                }
+                let new_backward: usize = (0..k)
+                    .filter(|&j| set & (1 << j) != 0)
+                    .map(|j| row[j])
+                    .sum();
+                let next = set | (1 << i);
+                if cost[set] + new_backward < cost[next] {
+                    cost[next] = cost[set] + new_backward;
+                    last[next] = i;
+                }
+            }
+        }
+        let mut order = Vec::with_capacity(k);
+        let mut set = (1 << k) - 1;
+        while set != 0 {
+            order.push(component[last[set]]);
+            set &= !(1 << last[set]);
+        }
+        order.reverse();
+        order
+    }
+    /// Order the vertices of a component with the heuristic of Eades, Lin and Smyth:
+    /// sinks go last, sources first, and otherwise the vertex with the largest
+    /// out-degree minus in-degree goes first. Weak edges are never backward.
+    fn greedy_order(&self, component: &[u32]) -> Vec<u32> {
+        let mut remaining = component.to_vec();
+        let (mut first, mut last) = (Vec::new(), Vec::new());
+        // Return the out- and in-degrees of `v` and whether it has weak edges out and in.
+        let degrees = |v: u32, remaining: &[u32]| {
+            let count_in = |adj_list: &BTreeMap<u32, Vec<u32>>| {
+                remaining
+                    .iter()
+                    .map(|u| adj_list[u].iter().filter(|&&w| w == v).count())
+                    .sum::<usize>()
+            };
+            let out_degree = self.adj_list[&v]
+                .iter()
+                .filter(|w| remaining.binary_search(w).is_ok())
+                .count();
+            let weak_out = self.weak_adj_list[&v]
+                .iter()
+                .any(|w| remaining.binary_search(w).is_ok());
+            (
+                out_degree,
+                count_in(&self.adj_list),
+                weak_out,
+                count_in(&self.weak_adj_list) > 0,
+            )
+        };
+        while !remaining.is_empty() {
+            let all_degrees: Vec<_> = remaining.iter().map(|&v| degrees(v, &remaining)).collect();
+            let i = if let Some(i) = all_degrees
+                .iter()
+                .position(|&(out, _, weak_out, _)| out == 0 && !weak_out)
+            {
+                last.push(remaining[i]);
+                i
+            } else if let Some(i) = all_degrees
+                .iter()
+                .position(|&(_, into, _, weak_in)| into == 0 && !weak_in)
+            {
+                first.push(remaining[i]);
+                i
+            } else {
+                let i = (0..remaining.len())
+                    .filter(|&i| !all_degrees[i].3)
+                    .max_by_key(|&i| all_degrees[i].0 as isize - all_degrees[i].1 as isize)
+                    .unwrap();
+                first.push(remaining[i]);
+                i
+            };
+            remaining.remove(i);
+        }
+        last.reverse();
+        first.extend(last);
+        first
+    }
+}
+
+/// Return the strongly connected components of a graph given by adjacency lists
+/// (Tarjan's algorithm), each component after the ones it has edges to.
+pub(crate) fn strongly_connected_components(adj_list: &BTreeMap<u32, Vec<u32>>) -> Vec<Vec<u32>> {
+    let mut index = BTreeMap::new();
+    let mut low_link = BTreeMap::new();
+    let mut stack = Vec::new();
+    // Whether each vertex is on the stack, by vertex.
+    let n = adj_list.keys().next_back().map_or(0, |&u| u as usize + 1);
+    let mut on_stack = vec![false; n];
+    let mut components = Vec::new();
+    for &root in adj_list.keys() {
+        if index.contains_key(&root) {
+            continue;
+        }
+        // Explicit recursion stack of (vertex, next edge to explore).
+        let mut calls = vec![(root, 0)];
+        while let Some(&(u, i)) = calls.last() {
+            if i == 0 {
+                index.insert(u, index.len());
+                low_link.insert(u, index[&u]);
+                stack.push(u);
+                on_stack[u as usize] = true;
+            }
+            match adj_list[&u].get(i) {
+                Some(&v) => {
+                    calls.last_mut().unwrap().1 += 1;
+                    if !index.contains_key(&v) {
+                        calls.push((v, 0));
+                    } else if on_stack[v as usize] {
+                        let low = low_link[&u].min(index[&v]);
+                        low_link.insert(u, low);
+                    }
+                }
+                None => {
+                    calls.pop();
+                    if let Some(&(parent, _)) = calls.last() {
+                        let low = low_link[&parent].min(low_link[&u]);
+                        low_link.insert(parent, low);
+                    }
+                    if low_link[&u] == index[&u] {
+                        let start = stack.iter().rposition(|&w| w == u).unwrap();
+                        let mut component = stack.split_off(start);
+                        for &w in &component {
+                            on_stack[w as usize] = false;
+                        }
+                        component.sort_unstable();
+                        components.push(component);
+                    }
+                }
+            }
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    /// Minimum feedback arc set size by trying every order.
+    fn brute_force(graph: &DependencyGraph) -> usize {
+        let n = graph.count_vertices() as u32;
+        (1..=n)
+            .permutations(n as usize)
+            .map(|order| {
+                graph
+                    .edges()
+                    .filter(|(u, v)| {
+                        order.iter().position(|w| w == u) > order.iter().position(|w| w == v)
+                    })
+                    .count()
+            })
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_strongly_connected_components() {
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4)], 6);
+        let mut components = g.strongly_connected_components();
+        components.sort();
+        assert_eq!(components, vec![vec![1, 2, 3], vec![4, 5], vec![6]]);
+    }
+    #[test]
+    fn test_feedback_arc_set() {
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4)], 6);
+        let fas = g.feedback_arc_set();
+        assert_eq!(fas.edges.len(), 2);
+        assert!(fas.exact);
+        assert!(g.without_edges(&fas.edges).is_acyclic());
+        let acyclic = DependencyGraph::new(vec![(1, 2), (1, 3)], 3);
+        assert_eq!(acyclic.feedback_arc_set().edges, vec![]);
+    }
+    #[test]
+    fn test_weak_edges_kept() {
+        // 1 and 2 share a page so (1, 2) goes, and (4, 3) is cheaper than the weak edge.
+        let g = DependencyGraph::builder(4)
+            .weak_edges(vec![(1, 2), (2, 1), (3, 4)])
+            .edges(vec![(1, 2), (2, 3), (4, 3)])
+            .build()
+            .unwrap();
+        let fas = g.feedback_arc_set();
+        assert_eq!(fas.edges, vec![(1, 2), (4, 3)]);
+        assert!(fas.exact);
+        assert!(g.without_edges(&fas.edges).is_acyclic());
+    }
+    #[test]
+    fn test_against_brute_force() {
+        let mut state = 7u64;
+        for _ in 0..30 {
+            let mut edges = Vec::new();
+            for _ in 0..9 {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let (u, v) = ((state >> 33) % 6 + 1, (state >> 45) % 6 + 1);
+                if u != v {
+                    edges.push((u as u32, v as u32));
+                }
+            }
+            let g = DependencyGraph::new(edges, 6);
+            let fas = g.feedback_arc_set();
+            assert_eq!(fas.edges.len(), brute_force(&g));
+            assert!(g.without_edges(&fas.edges).is_acyclic());
+        }
+    }
+    #[test]
+    fn test_greedy() {
+        // A long cycle with chords, above the exact threshold.
+        let n = MAX_EXACT_COMPONENT as u32 + 4;
+        let mut edges: Vec<_> = (1..n).map(|i| (i, i + 1)).collect();
+        edges.push((n, 1));
+        edges.push((n - 2, 3));
+        let g = DependencyGraph::new(edges, n as usize);
+        let fas = g.feedback_arc_set();
+        assert!(!fas.exact);
+        assert!(g.without_edges(&fas.edges).is_acyclic());
+    }
+}
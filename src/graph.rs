@@ -0,0 +1,591 @@
+use crate::error::GraphError;
+use crate::feedback::strongly_connected_components;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Directed graph data structure by adjacency lists
+///
+/// Vertices are the photos `1..=n_photos` and an edge `(u, v)`
+/// means that photo `u` must be on a page before photo `v`.
+/// A weak edge `(u, v)` only means that photo `v` is not on a page before photo `u`,
+/// and an edge with a lag `l` that photo `v` is at least `l` pages after photo `u`.
+/// Photos of an apart pair `(u, v)` cannot share a page, and the `last` photos
+/// are on the last page, as if every other photo had a weak edge to them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyGraph {
+    pub(crate) adj_list: BTreeMap<u32, Vec<u32>>,
+    pub(crate) weak_adj_list: BTreeMap<u32, Vec<u32>>,
+    /// Lags of the edges `(u, v)` whose lag is more than one page.
+    pub(crate) lags: BTreeMap<(u32, u32), usize>,
+    /// Apart pairs `(u, v)` with `u < v`, sorted.
+    pub(crate) apart: Vec<(u32, u32)>,
+    /// Photos on the last page, sorted.
+    pub(crate) last: Vec<u32>,
+}
+
+/// Builder for a `DependencyGraph`, checking the edges when building.
+#[derive(Clone, Debug)]
+pub struct DependencyGraphBuilder {
+    n_photos: usize,
+    edges: Vec<(u32, u32)>,
+    weak_edges: Vec<(u32, u32)>,
+    lags: Vec<(u32, u32, usize)>,
+    apart: Vec<(u32, u32)>,
+    last: Vec<u32>,
+}
+
+impl DependencyGraphBuilder {
+    /// Add the constraint that photo `u` comes on a page before photo `v`.
+    pub fn edge(mut self, u: u32, v: u32) -> Self {
+        self.edges.push((u, v));
+        self
+    }
+    /// Add several edges at once.
+    pub fn edges<I: IntoIterator<Item = (u32, u32)>>(mut self, edges: I) -> Self {
+        self.edges.extend(edges);
+        self
+    }
+    /// Add the constraint that photo `v` is not on a page before photo `u`.
+    pub fn weak_edge(mut self, u: u32, v: u32) -> Self {
+        self.weak_edges.push((u, v));
+        self
+    }
+    /// Add several weak edges at once.
+    pub fn weak_edges<I: IntoIterator<Item = (u32, u32)>>(mut self, edges: I) -> Self {
+        self.weak_edges.extend(edges);
+        self
+    }
+    /// Add the constraint that photo `v` is at least `lag` pages after photo `u`:
+    /// a lag of 0 is a weak edge and a lag of 1 a plain edge.
+    pub fn lagged_edge(mut self, u: u32, v: u32, lag: usize) -> Self {
+        match lag {
+            0 => self.weak_edges.push((u, v)),
+            _ => self.edges.push((u, v)),
+        }
+        if lag > 1 {
+            self.lags.push((u, v, lag));
+        }
+        self
+    }
+    /// Add several edges with their lag at once.
+    pub fn lagged_edges<I: IntoIterator<Item = (u32, u32, usize)>>(mut self, edges: I) -> Self {
+        for (u, v, lag) in edges {
+            self = self.lagged_edge(u, v, lag);
+        }
+        self
+    }
+    /// Add the constraint that the photos of `group` share a page,
+    /// as a cycle of weak edges through them.
+    pub fn together(mut self, group: &[u32]) -> Self {
+        let mut group = group.to_vec();
+        group.sort_unstable();
+        group.dedup();
+        if group.len() > 1 {
+            let next = group.iter().copied().cycle().skip(1);
+            self.weak_edges.extend(group.iter().copied().zip(next));
+        }
+        self
+    }
+    /// Add the constraint that photo `photo` is on the last page.
+    pub fn last(mut self, photo: u32) -> Self {
+        self.last.push(photo);
+        self
+    }
+    /// Add the constraint that photos `u` and `v` are on different pages.
+    pub fn apart(mut self, u: u32, v: u32) -> Self {
+        self.apart.push((u, v));
+        self
+    }
+    pub fn build(self) -> Result<DependencyGraph, GraphError> {
+        let mut adj_list = BTreeMap::new();
+        for v in 1..=self.n_photos as u32 {
+            adj_list.insert(v, Vec::new());
+        }
+        let mut weak_adj_list = adj_list.clone();
+        for (edges, lists) in [
+            (self.edges, &mut adj_list),
+            (self.weak_edges, &mut weak_adj_list),
+        ] {
+            for (u, v) in edges {
+                for photo in [u, v] {
+                    if !lists.contains_key(&photo) {
+                        return Err(GraphError::PhotoOutOfRange {
+                            photo,
+                            n_photos: self.n_photos,
+                        });
+                    }
+                }
+                if u == v {
+                    return Err(GraphError::SelfLoop { photo: u });
+                }
+                lists.get_mut(&u).unwrap().push(v)
+            }
+        }
+        let mut apart = Vec::new();
+        for (u, v) in self.apart {
+            for photo in [u, v] {
+                if !adj_list.contains_key(&photo) {
+                    return Err(GraphError::PhotoOutOfRange {
+                        photo,
+                        n_photos: self.n_photos,
+                    });
+                }
+            }
+            if u == v {
+                return Err(GraphError::SelfApart { photo: u });
+            }
+            apart.push((u.min(v), u.max(v)));
+        }
+        apart.sort_unstable();
+        apart.dedup();
+        let mut last = self.last;
+        if let Some(&photo) = last.iter().find(|photo| !adj_list.contains_key(photo)) {
+            return Err(GraphError::PhotoOutOfRange {
+                photo,
+                n_photos: self.n_photos,
+            });
+        }
+        last.sort_unstable();
+        last.dedup();
+        // The edges with a lag have been checked with the others.
+        let mut lags = BTreeMap::new();
+        for (u, v, lag) in self.lags {
+            let entry = lags.entry((u, v)).or_insert(lag);
+            *entry = lag.max(*entry);
+        }
+        Ok(DependencyGraph {
+            adj_list,
+            weak_adj_list,
+            lags,
+            apart,
+            last,
+        })
+    }
+}
+
+impl DependencyGraph {
+    /// Start building a graph on the photos `1..=n_photos`.
+    pub fn builder(n_photos: usize) -> DependencyGraphBuilder {
+        DependencyGraphBuilder {
+            n_photos,
+            edges: Vec::new(),
+            weak_edges: Vec::new(),
+            lags: Vec::new(),
+            apart: Vec::new(),
+            last: Vec::new(),
+        }
+    }
+    /// Build a graph from valid edges (panics otherwise).
+    #[cfg(test)]
+    pub(crate) fn new(edges: Vec<(u32, u32)>, n_vertices: usize) -> Self {
+        Self::builder(n_vertices).edges(edges).build().unwrap()
+    }
+    /// Return the number of photos.
+    pub fn count_vertices(&self) -> usize {
+        self.adj_list.len()
+    }
+    /// Iterate over the edges.
+    pub fn edges(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.adj_list
+            .iter()
+            .flat_map(|(&u, neighbourhood)| neighbourhood.iter().map(move |&v| (u, v)))
+    }
+    /// Iterate over the weak edges.
+    pub fn weak_edges(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.weak_adj_list
+            .iter()
+            .flat_map(|(&u, neighbourhood)| neighbourhood.iter().map(move |&v| (u, v)))
+    }
+    /// Return the number of pages photo `v` is at least after photo `u`,
+    /// for an edge `(u, v)`.
+    pub fn lag(&self, u: u32, v: u32) -> usize {
+        self.lags.get(&(u, v)).copied().unwrap_or(1)
+    }
+    /// Iterate over the edges whose lag is more than one page, with their lag.
+    pub fn lagged_edges(&self) -> impl Iterator<Item = (u32, u32, usize)> + '_ {
+        self.lags.iter().map(|(&(u, v), &lag)| (u, v, lag))
+    }
+    /// Iterate over the apart pairs `(u, v)`, with `u < v`.
+    pub fn apart_pairs(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.apart.iter().copied()
+    }
+    /// Iterate over the photos on the last page.
+    pub fn last_photos(&self) -> impl Iterator<Item = u32> + '_ {
+        self.last.iter().copied()
+    }
+    /// Return if photo `v` is on the last page.
+    pub(crate) fn is_last(&self, v: u32) -> bool {
+        self.last.binary_search(&v).is_ok()
+    }
+    /// Return the graph with the end of the album as a photo 0, with a weak edge
+    /// from every photo to it and from it to the photos on the last page: paths
+    /// through it are those through the weak edges the last photos stand for.
+    pub(crate) fn with_end(&self) -> DependencyGraph {
+        let mut graph = self.clone();
+        if !self.last.is_empty() {
+            for neighbourhood in graph.weak_adj_list.values_mut() {
+                neighbourhood.push(0);
+            }
+            graph.adj_list.insert(0, Vec::new());
+            graph.weak_adj_list.insert(0, graph.last.split_off(0));
+        }
+        graph
+    }
+    /// Return if two of the (sorted) `photos` are apart.
+    pub(crate) fn has_apart_pair(&self, photos: &[u32]) -> bool {
+        self.apart
+            .iter()
+            .any(|(u, v)| photos.binary_search(u).is_ok() && photos.binary_search(v).is_ok())
+    }
+    /// Return the set of vertices with no ingoing (strict) edge.
+    pub(crate) fn roots(&self) -> BTreeSet<u32> {
+        let mut result: BTreeSet<u32> = self.adj_list.keys().copied().collect();
+        for neighbourhood in self.adj_list.values() {
+            for u in neighbourhood.iter() {
+                result.remove(u);
+            }
+        }
+        result
+    }
+    /// Return the roots that can go on the next page, that is whose weak predecessors
+    /// are all roots that can go on the next page.
+    pub(crate) fn ready(&self) -> BTreeSet<u32> {
+        self.ready_where(|_| true)
+    }
+    /// Return the roots that can go on the next page among the `released` ones.
+    pub(crate) fn ready_where(&self, released: impl Fn(u32) -> bool) -> BTreeSet<u32> {
+        let mut result = self.roots();
+        result.retain(|&v| released(v));
+        loop {
+            let mut blocked: Vec<u32> = self
+                .weak_adj_list
+                .iter()
+                .filter(|(u, _)| !result.contains(u))
+                .flat_map(|(_, neighbourhood)| neighbourhood)
+                .copied()
+                .filter(|v| result.contains(v))
+                .collect();
+            // The last photos wait for every other one.
+            if result.len() < self.adj_list.len() {
+                blocked.extend(self.last_photos().filter(|v| result.contains(v)));
+            }
+            if blocked.is_empty() {
+                return result;
+            }
+            for v in blocked {
+                result.remove(&v);
+            }
+        }
+    }
+    /// Return the set of vertices with no edge (in- or outgoig) and not apart from another one
+    pub(crate) fn isolated_vertices(&self) -> Vec<u32> {
+        if self.last_photos().any(|v| self.adj_list.contains_key(&v)) {
+            return Vec::new();
+        }
+        let weak_targets: BTreeSet<u32> = self.weak_adj_list.values().flatten().copied().collect();
+        let apart: BTreeSet<u32> = self
+            .apart
+            .iter()
+            .filter(|(u, v)| self.adj_list.contains_key(u) && self.adj_list.contains_key(v))
+            .flat_map(|&(u, v)| [u, v])
+            .collect();
+        self.roots()
+            .into_iter()
+            .filter(|v| {
+                self.adj_list[v].is_empty()
+                    && self.weak_adj_list[v].is_empty()
+                    && !weak_targets.contains(v)
+                    && !apart.contains(v)
+            })
+            .collect()
+    }
+    /// Remove a vertex.
+    pub(crate) fn remove(&mut self, vertex: u32) {
+        self.adj_list.remove(&vertex);
+        self.weak_adj_list.remove(&vertex);
+    }
+    /// Return the vertices in an order compatible with the edges of both kinds,
+    /// or `None` if the graph has a cycle, even of weak edges only.
+    pub(crate) fn topological_order(&self) -> Option<Vec<u32>> {
+        let mut graph = self.clone();
+        let mut result = Vec::new();
+        while !graph.adj_list.is_empty() {
+            let n_placed = result.len();
+            for u in graph.ready() {
+                let last = graph.is_last(u) && graph.adj_list.len() > 1;
+                if !last && !graph.weak_adj_list.values().flatten().any(|&v| v == u) {
+                    graph.remove(u);
+                    result.push(u);
+                }
+            }
+            if result.len() == n_placed {
+                return None;
+            }
+        }
+        Some(result)
+    }
+    /// Return a directed cycle `[u_1, ..., u_k]` (with an edge from `u_k` back to `u_1`)
+    /// going through at least one strict edge and starting from its smallest photo,
+    /// or `None` if there is no such cycle.
+    ///
+    /// Cycles of weak edges only are not returned: they just force their photos
+    /// onto the same page.
+    pub fn find_cycle(&self) -> Option<Vec<u32>> {
+        // The first strict edge `(u, v)` inside a strongly connected component closes
+        // a cycle with the shortest path back from `v` to `u`.
+        let graph = self.with_end();
+        let component = graph.components();
+        let (u, v) = graph.edges().find(|(u, v)| component[u] == component[v])?;
+        let mut cycle = graph.shortest_path(&[v], |w| w == u)?;
+        cycle.retain(|&w| w != 0);
+        let smallest = (0..cycle.len()).min_by_key(|&j| cycle[j]).unwrap();
+        cycle.rotate_left(smallest);
+        Some(cycle)
+    }
+    /// Return photos that must share a page, being linked by a cycle of weak edges,
+    /// with a chain of edges from one of them to one of them going through a strict edge,
+    /// or `None` if there is no such group.
+    pub fn find_separated_group(&self) -> Option<(Vec<u32>, Vec<u32>)> {
+        // Such a chain closes a cycle, so its strict edge is inside the strongly
+        // connected component of the group: keep the first one of each component.
+        let graph = self.with_end();
+        let component = graph.components();
+        let mut inner_edges = BTreeMap::new();
+        for (u, v) in graph.edges() {
+            if component[&u] == component[&v] {
+                inner_edges.entry(component[&u]).or_insert((u, v));
+            }
+        }
+        for mut group in strongly_connected_components(&graph.weak_adj_list) {
+            group.retain(|&w| w != 0);
+            if group.len() <= 1 {
+                continue;
+            }
+            if let Some(&(u, v)) = inner_edges.get(&component[&group[0]]) {
+                let in_group = |w: u32| group.binary_search(&w).is_ok();
+                let mut chain = graph.shortest_path(&group, |w| w == u)?;
+                chain.extend(graph.shortest_path(&[v], in_group)?);
+                chain.retain(|&w| w != 0);
+                return Some((group, chain));
+            }
+        }
+        None
+    }
+    /// Return the index of the strongly connected component of each photo,
+    /// following edges of both kinds.
+    fn components(&self) -> BTreeMap<u32, usize> {
+        let mut adj_list = self.adj_list.clone();
+        for (u, neighbourhood) in &self.weak_adj_list {
+            adj_list.entry(*u).or_default().extend(neighbourhood);
+        }
+        let components = strongly_connected_components(&adj_list);
+        let mut component = BTreeMap::new();
+        for (i, photos) in components.into_iter().enumerate() {
+            component.extend(photos.into_iter().map(|photo| (photo, i)));
+        }
+        component
+    }
+    /// Return a shortest path, following edges of both kinds, from one of `sources`
+    /// to a photo satisfying `target`.
+    fn shortest_path(&self, sources: &[u32], target: impl Fn(u32) -> bool) -> Option<Vec<u32>> {
+        let mut parent: BTreeMap<u32, Option<u32>> = sources.iter().map(|&v| (v, None)).collect();
+        let mut queue: VecDeque<u32> = sources.iter().copied().collect();
+        while let Some(w) = queue.pop_front() {
+            if target(w) {
+                let mut path = vec![w];
+                while let Some(previous) = parent[path.last().unwrap()] {
+                    path.push(previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &x in self.adj_list[&w].iter().chain(&self.weak_adj_list[&w]) {
+                parent.entry(x).or_insert_with(|| {
+                    queue.push_back(x);
+                    Some(w)
+                });
+            }
+        }
+        None
+    }
+    /// Compute if no directed cycle goes through a strict edge,
+    /// that is if the constraints can be satisfied with large enough pages.
+    pub fn is_acyclic(&self) -> bool {
+        let mut graph = self.clone();
+        while !graph.adj_list.is_empty() {
+            let ready = graph.ready();
+            if ready.is_empty() {
+                return false;
+            } else {
+                for &u in &ready {
+                    graph.remove(u)
+                }
+            }
+        }
+        true
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roots() {
+        let g = DependencyGraph::new(vec![(1, 4), (3, 2), (5, 3)], 6);
+        let expected: BTreeSet<_> = vec![1, 5, 6].into_iter().collect();
+        assert_eq!(g.roots(), expected);
+    }
+    #[test]
+    fn test_isolated_vertices() {
+        let g = DependencyGraph::new(vec![(1, 4), (3, 2), (5, 3), (5, 7)], 9);
+        let expected = vec![6, 8, 9];
+        assert_eq!(g.isolated_vertices(), expected);
+    }
+    #[test]
+    fn test_is_acyclic() {
+        let c4 = DependencyGraph::new(vec![(1, 4), (4, 2), (2, 3), (3, 1)], 4);
+        assert!(!c4.is_acyclic());
+        let transitive_tournament = DependencyGraph::new(vec![(1, 3), (1, 2), (2, 3)], 3);
+        assert!(transitive_tournament.is_acyclic());
+    }
+    #[test]
+    fn test_find_cycle() {
+        let c4 = DependencyGraph::new(vec![(1, 4), (4, 2), (2, 3), (3, 1)], 4);
+        assert_eq!(c4.find_cycle(), Some(vec![1, 4, 2, 3]));
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (5, 3), (3, 6), (6, 5)], 6);
+        assert_eq!(g.find_cycle(), Some(vec![3, 6, 5]));
+        let transitive_tournament = DependencyGraph::new(vec![(1, 3), (1, 2), (2, 3)], 3);
+        assert_eq!(transitive_tournament.find_cycle(), None);
+        // Only the last of many edges closes a cycle.
+        let n = 20_000;
+        let edges = (1..n).map(|u| (u, u + 1)).chain([(n, n - 1)]);
+        let chain = DependencyGraph::new(edges.collect(), n as usize);
+        assert_eq!(chain.find_cycle(), Some(vec![n - 1, n]));
+    }
+    #[test]
+    fn test_weak_edges() {
+        // 1 and 2 share a page, 3 comes strictly after them and 4 not before 3.
+        let g = DependencyGraph::builder(5)
+            .weak_edges(vec![(1, 2), (2, 1), (3, 4)])
+            .edge(2, 3)
+            .build()
+            .unwrap();
+        assert!(g.is_acyclic());
+        assert_eq!(g.find_cycle(), None);
+        assert_eq!(g.ready(), vec![1, 2, 5].into_iter().collect());
+        assert_eq!(g.isolated_vertices(), vec![5]);
+        let mixed = DependencyGraph::builder(3)
+            .weak_edges(vec![(1, 2), (3, 1)])
+            .edge(2, 3)
+            .build()
+            .unwrap();
+        assert!(!mixed.is_acyclic());
+        assert_eq!(mixed.find_cycle(), Some(vec![1, 2, 3]));
+        assert!(mixed.ready().is_empty());
+    }
+    #[test]
+    fn test_lagged_edges() {
+        let g = DependencyGraph::builder(3)
+            .lagged_edges(vec![(1, 2, 0), (2, 3, 1), (1, 3, 3), (1, 3, 2)])
+            .build()
+            .unwrap();
+        assert_eq!(g.weak_edges().collect::<Vec<_>>(), vec![(1, 2)]);
+        assert_eq!(g.edges().collect::<Vec<_>>(), vec![(1, 3), (1, 3), (2, 3)]);
+        assert_eq!(g.lagged_edges().collect::<Vec<_>>(), vec![(1, 3, 3)]);
+        assert_eq!((g.lag(1, 3), g.lag(2, 3)), (3, 1));
+        let g = g.without_edges(&[(1, 3)]);
+        assert_eq!(g.lag(1, 3), 3);
+        assert_eq!(g.without_edges(&[(1, 3)]).lagged_edges().count(), 0);
+    }
+    #[test]
+    fn test_together() {
+        let g = DependencyGraph::builder(4)
+            .together(&[3, 1, 2])
+            .edge(4, 1)
+            .build()
+            .unwrap();
+        let weak: Vec<_> = g.weak_edges().collect();
+        assert_eq!(weak, vec![(1, 2), (2, 3), (3, 1)]);
+        assert_eq!(g.find_separated_group(), None);
+        assert_eq!(g.topological_order(), None);
+        let chain = DependencyGraph::builder(4)
+            .weak_edges(vec![(1, 2), (2, 3)])
+            .edge(4, 1)
+            .build()
+            .unwrap();
+        assert_eq!(chain.topological_order(), Some(vec![4, 1, 2, 3]));
+        let separated = DependencyGraph::builder(5)
+            .together(&[1, 2, 3])
+            .edges(vec![(2, 4), (4, 5), (5, 3)])
+            .build()
+            .unwrap();
+        let chain = vec![2, 4, 5, 3];
+        assert_eq!(
+            separated.find_separated_group(),
+            Some((vec![1, 2, 3], chain))
+        );
+    }
+    #[test]
+    fn test_last() {
+        // 3 and 4 share the last page, and 2 with them, having a weak edge to 4.
+        let g = DependencyGraph::builder(5)
+            .last(4)
+            .last(3)
+            .weak_edge(4, 2)
+            .edge(1, 2)
+            .build()
+            .unwrap();
+        assert_eq!(g.weak_edges().count(), 1);
+        assert_eq!(g.last_photos().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(g.ready(), vec![1, 5].into_iter().collect());
+        assert!(g.isolated_vertices().is_empty());
+        assert!(g.is_acyclic());
+        assert_eq!(g.topological_order(), None);
+        assert_eq!(g.find_separated_group(), None);
+        // The photos after a last photo would come after the last page.
+        let after = DependencyGraph::builder(3)
+            .last(1)
+            .edge(1, 3)
+            .build()
+            .unwrap();
+        assert!(!after.is_acyclic());
+        assert_eq!(after.find_cycle(), Some(vec![1, 3]));
+        let chain = DependencyGraph::builder(3)
+            .last(1)
+            .edges(vec![(2, 3), (3, 1)])
+            .build()
+            .unwrap();
+        assert_eq!(chain.topological_order(), Some(vec![2, 3, 1]));
+        let out_of_range = DependencyGraph::builder(3).last(4).build();
+        assert_eq!(
+            out_of_range,
+            Err(GraphError::PhotoOutOfRange {
+                photo: 4,
+                n_photos: 3
+            })
+        );
+    }
+    #[test]
+    fn test_builder() {
+        let g = DependencyGraph::builder(3).edge(1, 2).edge(1, 3).build();
+        assert_eq!(g.unwrap().edges().collect::<Vec<_>>(), vec![(1, 2), (1, 3)]);
+        let out_of_range = DependencyGraph::builder(3).edge(1, 4).build();
+        assert_eq!(
+            out_of_range,
+            Err(GraphError::PhotoOutOfRange {
+                photo: 4,
+                n_photos: 3
+            })
+        );
+        let self_loop = DependencyGraph::builder(3).edge(2, 2).build();
+        assert_eq!(self_loop, Err(GraphError::SelfLoop { photo: 2 }));
+        let apart = DependencyGraph::builder(3).apart(3, 1).apart(1, 3).build();
+        assert_eq!(
+            apart.unwrap().apart_pairs().collect::<Vec<_>>(),
+            vec![(1, 3)]
+        );
+        let self_apart = DependencyGraph::builder(3).apart(2, 2).build();
+        assert_eq!(self_apart, Err(GraphError::SelfApart { photo: 2 }));
+    }
+}
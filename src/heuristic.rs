@@ -0,0 +1,599 @@
+//! Heuristic for the albums too large for the exact search: list scheduling
+//! by critical path, followed by local improvement.
+//!
+//! Photos that must share a page (linked by a cycle of weak edges) are placed together
+//! as one unit. Page after page, the units whose predecessors are placed
+//! and whose lags are over go on the page by earliest deadline, then by longest chain
+//! of pages still to follow, as long as they fit. The units that must share a spread
+//! come first once one of them is placed, so as to complete the spread, and start on
+//! a later spread when they cannot. This takes O((n + edges) log n) time when each unit
+//! fits on the page it is ready for. Otherwise the units left out are tried again on every
+//! page, and the ready units sorted again whenever a group on the same spread starts:
+//! O(pages × n log n + edges) time at worst. The units of the last page and those they
+//! wait for are then moved up in priority and the units scheduled again, a few times,
+//! keeping the best schedule. A group that does not fit on its spread also schedules
+//! all the units again, up to once by unit.
+
+use crate::bound::min_pages_bound;
+use crate::feedback::strongly_connected_components;
+use crate::instance::{Instance, Schedule};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+
+/// Number of list schedules tried at most.
+const ROUNDS: usize = 8;
+
+/// Why a list schedule failed.
+enum Failure {
+    /// A group of units on the same spread did not fit on the spread ending before this page.
+    Group(usize, usize),
+    /// A deadline, chapter or spread could not be met.
+    Stuck,
+}
+
+/// Photos placed on the same page, and their constraints with the other units.
+#[derive(Clone, Debug, Default)]
+struct Unit {
+    photos: Vec<u32>,
+    size: usize,
+    chapter: Option<usize>,
+    /// First page (from 0) allowed by the pins.
+    release: usize,
+    /// Page (from 0) the unit must come before, earlier when its successors
+    /// have deadlines too.
+    deadline: Option<usize>,
+    /// Units after this one, with the lag in pages (0 for a weak edge).
+    successors: Vec<(usize, usize)>,
+    /// Units that cannot be on the same page.
+    apart: Vec<usize>,
+    /// Units that cannot be on the same spread.
+    spread_apart: Vec<usize>,
+    /// Group of units on the same spread, if any.
+    spread_group: Option<usize>,
+    /// Fewest pages from the page of this unit to the last one.
+    tail: usize,
+    /// The unit holds the photos on the last page, and comes after every other one.
+    last: bool,
+}
+
+/// Return a schedule of an instance, or `None` if the heuristic finds none
+/// or one breaking a constraint of the instance.
+pub(crate) fn schedule(instance: &Instance) -> Option<Schedule> {
+    let mut units = units(instance)?;
+    let mut predecessors = vec![Vec::new(); units.len()];
+    for (u, unit) in units.iter().enumerate() {
+        for &(v, _) in &unit.successors {
+            predecessors[v].push(u);
+        }
+    }
+    let bound = min_pages_bound(instance);
+    let mut boost = vec![0; units.len()];
+    let mut best: Option<Vec<Vec<usize>>> = None;
+    let (mut round, mut delays) = (0, units.len());
+    while round < ROUNDS {
+        let pages = match list_schedule(instance, &units, &boost) {
+            Ok(pages) => pages,
+            Err(Failure::Group(g, page)) if delays > 0 => {
+                // Start the group on the next spread.
+                delays -= 1;
+                for unit in units.iter_mut().filter(|unit| unit.spread_group == Some(g)) {
+                    unit.release = unit.release.max(page);
+                }
+                continue;
+            }
+            Err(_) => break,
+        };
+        round += 1;
+        let last = pages.last().cloned().unwrap_or_default();
+        if best.as_ref().is_none_or(|best| pages.len() < best.len()) {
+            best = Some(pages);
+        }
+        if best.as_ref().is_some_and(|best| best.len() <= bound) {
+            break;
+        }
+        // Move up the units of the last page and those they wait for.
+        let mut seen = vec![false; units.len()];
+        let mut stack = last;
+        while let Some(u) = stack.pop() {
+            if !seen[u] {
+                seen[u] = true;
+                boost[u] += 1;
+                stack.extend(&predecessors[u]);
+            }
+        }
+    }
+    let schedule: Schedule = best?
+        .into_iter()
+        .map(|page| {
+            let mut photos: Vec<u32> = page
+                .into_iter()
+                .flat_map(|u| units[u].photos.iter().copied())
+                .collect();
+            photos.sort_unstable();
+            photos
+        })
+        .collect();
+    Some(schedule).filter(|schedule| instance.is_valid_schedule(schedule))
+}
+
+/// Union-find: return the representative of `i`.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Merge the elements of each group, numbering the classes from 0. Return the class
+/// of each element of `0..n`, and the number of classes.
+fn classes(n: usize, groups: impl Iterator<Item = Vec<usize>>) -> (Vec<usize>, usize) {
+    let mut parent: Vec<usize> = (0..n).collect();
+    for group in groups {
+        for pair in group.windows(2) {
+            let u = find(&mut parent, pair[0]);
+            let v = find(&mut parent, pair[1]);
+            parent[u] = v;
+        }
+    }
+    let mut index = BTreeMap::new();
+    let class = (0..n)
+        .map(|i| {
+            let root = find(&mut parent, i);
+            let n_classes = index.len();
+            *index.entry(root).or_insert(n_classes)
+        })
+        .collect();
+    (class, index.len())
+}
+
+/// Group the photos into units, numbered in an order where every edge goes forward,
+/// or return `None` if two photos of a unit have an edge or are apart.
+fn units(instance: &Instance) -> Option<Vec<Unit>> {
+    let graph = &instance.graph;
+    let n_photos = graph.count_vertices();
+    // The photos with a weak edge to a last photo are on the last page too.
+    let sccs = strongly_connected_components(&graph.with_end().weak_adj_list);
+    let photos = |group: Vec<u32>| {
+        let photos = group.into_iter().filter(|&photo| photo != 0);
+        photos.map(|photo| photo as usize - 1).collect()
+    };
+    let (unit_of, n_units) = classes(n_photos, sccs.into_iter().map(photos));
+    let unit = |photo: u32| unit_of[photo as usize - 1];
+    let mut units = vec![Unit::default(); n_units];
+    for photo in 1..=n_photos as u32 {
+        let pin = instance.pin(photo);
+        let unit = &mut units[unit(photo)];
+        unit.photos.push(photo);
+        unit.size += instance.size(photo);
+        unit.release = unit.release.max(pin.release - 1);
+        if let Some(deadline) = pin.deadline {
+            unit.deadline = Some(unit.deadline.map_or(deadline, |d| d.min(deadline)));
+        }
+        if let Some(chapter) = instance.chapter(photo) {
+            if unit.chapter.is_some_and(|c| c != chapter) {
+                return None;
+            }
+            unit.chapter = Some(chapter);
+        }
+    }
+    let mut lags: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    let weak = graph.weak_edges().map(|(u, v)| (u, v, 0));
+    let strict = graph.edges().map(|(u, v)| (u, v, graph.lag(u, v)));
+    for (u, v, lag) in strict.chain(weak) {
+        match (unit(u), unit(v)) {
+            (x, y) if x == y && lag > 0 => return None,
+            (x, y) if x == y => {}
+            (x, y) => {
+                let entry = lags.entry((x, y)).or_insert(lag);
+                *entry = (*entry).max(lag);
+            }
+        }
+    }
+    for ((x, y), lag) in lags {
+        units[x].successors.push((y, lag));
+    }
+    if let Some(photo) = graph.last_photos().next() {
+        let x = unit(photo);
+        if !units[x].successors.is_empty() {
+            return None;
+        }
+        units[x].last = true;
+        // Every unit comes before the last one, and so before its deadline.
+        if let Some(deadline) = units[x].deadline {
+            for unit in &mut units {
+                unit.deadline = Some(unit.deadline.map_or(deadline, |d| d.min(deadline)));
+            }
+        }
+    }
+    let spread_apart = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| spreads.apart.iter().copied());
+    for (u, v) in graph.apart_pairs() {
+        let (x, y) = (unit(u), unit(v));
+        if x == y {
+            return None;
+        }
+        units[x].apart.push(y);
+        units[y].apart.push(x);
+    }
+    // The units on the same spread, numbered by the groups they form.
+    let together: Vec<Vec<usize>> = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| &spreads.together)
+        .map(|group| group.iter().map(|&photo| unit(photo)).collect())
+        .collect();
+    let (spread_group, _) = classes(n_units, together.iter().cloned());
+    for &u in together.iter().flatten() {
+        units[u].spread_group = Some(spread_group[u]);
+    }
+    for (u, v) in spread_apart {
+        let (x, y) = (unit(u), unit(v));
+        if x == y
+            || units[x]
+                .spread_group
+                .is_some_and(|g| units[y].spread_group == Some(g))
+        {
+            return None;
+        }
+        units[x].spread_apart.push(y);
+        units[y].spread_apart.push(x);
+    }
+    // Renumber the units in a topological order.
+    let mut n_predecessors = vec![0; units.len()];
+    for &(v, _) in units.iter().flat_map(|unit| &unit.successors) {
+        n_predecessors[v] += 1;
+    }
+    let mut order: Vec<usize> = (0..units.len())
+        .filter(|&u| n_predecessors[u] == 0)
+        .collect();
+    let mut i = 0;
+    while let Some(&u) = order.get(i) {
+        for &(v, _) in &units[u].successors {
+            n_predecessors[v] -= 1;
+            if n_predecessors[v] == 0 {
+                order.push(v);
+            }
+        }
+        i += 1;
+    }
+    if order.len() < units.len() {
+        return None;
+    }
+    let mut number = vec![0; units.len()];
+    for (i, &u) in order.iter().enumerate() {
+        number[u] = i;
+    }
+    let mut units: Vec<Unit> = order.into_iter().map(|u| units[u].clone()).collect();
+    for unit in &mut units {
+        for (v, _) in &mut unit.successors {
+            *v = number[*v];
+        }
+        for v in unit.apart.iter_mut().chain(&mut unit.spread_apart) {
+            *v = number[*v];
+        }
+    }
+    // Tails and deadlines go backwards from the last units.
+    for u in (0..units.len()).rev() {
+        let mut tail = 1;
+        let mut deadline = units[u].deadline;
+        for &(v, lag) in &units[u].successors {
+            tail = tail.max(lag + units[v].tail);
+            if let Some(after) = units[v].deadline {
+                let before = after.saturating_sub(lag);
+                deadline = Some(deadline.map_or(before, |d| d.min(before)));
+            }
+        }
+        units[u].tail = tail;
+        units[u].deadline = deadline;
+    }
+    Some(units)
+}
+
+/// Fill the pages one after the other with the ready units, in order of priority,
+/// the priority of each unit being raised by its `boost`. Return the units of
+/// each page, or why they could not be placed.
+fn list_schedule(
+    instance: &Instance,
+    units: &[Unit],
+    boost: &[usize],
+) -> Result<Vec<Vec<usize>>, Failure> {
+    let n_groups = units
+        .iter()
+        .filter_map(|unit| unit.spread_group)
+        .max()
+        .map_or(0, |g| g + 1);
+    // Slots and units of each group on the same spread still to place.
+    let mut group_size = vec![0; n_groups];
+    let mut group_left = vec![0; n_groups];
+    for unit in units {
+        if let Some(g) = unit.spread_group {
+            group_size[g] += unit.size;
+            group_left[g] += 1;
+        }
+    }
+    // The first page after the spread of each group started, which its units must precede.
+    let mut group_end: Vec<Option<usize>> = vec![None; n_groups];
+    let key = |u: usize, group_end: &[Option<usize>]| {
+        let unit = &units[u];
+        let end = unit.spread_group.and_then(|g| group_end[g]);
+        let deadline = unit.deadline.into_iter().chain(end).min();
+        Reverse((
+            deadline.unwrap_or(usize::MAX),
+            Reverse(unit.tail + boost[u]),
+            u,
+        ))
+    };
+    let same_spread = |p: usize, q: usize| {
+        instance
+            .spreads
+            .as_ref()
+            .is_some_and(|spreads| spreads.of_page(p) == spreads.of_page(q))
+    };
+    let spread_end = |page: usize| (page + 1..).find(|&q| !same_spread(page, q)).unwrap();
+    let mut n_predecessors = vec![0; units.len()];
+    for &(v, _) in units.iter().flat_map(|unit| &unit.successors) {
+        n_predecessors[v] += 1;
+    }
+    // The last unit waits for every other one.
+    let last = units.iter().position(|unit| unit.last);
+    if let Some(l) = last {
+        n_predecessors[l] += units.len() - 1;
+    }
+    let mut available: Vec<usize> = units.iter().map(|unit| unit.release).collect();
+    // Units ready to go on a page, and units waiting for a page.
+    let mut ready = BinaryHeap::new();
+    let mut later: BinaryHeap<_> = (0..units.len())
+        .filter(|&u| n_predecessors[u] == 0)
+        .map(|u| Reverse((available[u], u)))
+        .collect();
+    let mut chapter_left: BTreeMap<usize, usize> = BTreeMap::new();
+    for chapter in units.iter().filter_map(|unit| unit.chapter) {
+        *chapter_left.entry(chapter).or_insert(0) += 1;
+    }
+    let mut started = BTreeSet::new();
+    let mut current = None;
+    let mut page_of = vec![None; units.len()];
+    let mut pages = Vec::new();
+    let (mut n_placed, mut idle) = (0, 0);
+    while n_placed < units.len() {
+        let page = pages.len();
+        while let Some(&Reverse((at, u))) = later.peek() {
+            if at > page {
+                break;
+            }
+            later.pop();
+            ready.push(key(u, &group_end));
+        }
+        let mut room = instance.capacities.of_page(page);
+        let mut chapter = current;
+        let mut on_page = Vec::new();
+        let mut deferred = Vec::new();
+        while room > 0 {
+            let Some(Reverse((deadline, _, u))) = ready.pop() else {
+                break;
+            };
+            if deadline <= page {
+                return Err(Failure::Stuck);
+            }
+            let unit = &units[u];
+            // A group starts on a spread only if the spread has room for all of it.
+            let group_fits = unit.spread_group.is_none_or(|g| {
+                group_end[g].is_some()
+                    || group_size[g]
+                        <= room
+                            + (page + 1..spread_end(page))
+                                .map(|q| instance.capacities.of_page(q))
+                                .sum::<usize>()
+            });
+            let fits = unit.size <= room
+                && group_fits
+                && unit.chapter.is_none_or(|c| {
+                    chapter == Some(c) || (chapter.is_none() && !started.contains(&c))
+                })
+                && unit.apart.iter().all(|&v| page_of[v] != Some(page))
+                && unit
+                    .spread_apart
+                    .iter()
+                    .all(|&v| page_of[v].is_none_or(|p| !same_spread(p, page)));
+            if !fits {
+                deferred.push(u);
+                continue;
+            }
+            room -= unit.size;
+            page_of[u] = Some(page);
+            on_page.push(u);
+            n_placed += 1;
+            if let Some(c) = unit.chapter {
+                chapter = Some(c);
+                started.insert(c);
+                *chapter_left.get_mut(&c).unwrap() -= 1;
+            }
+            if let Some(g) = unit.spread_group {
+                group_left[g] -= 1;
+                if group_end[g].is_none() {
+                    // The rest of the group comes first, to end on this spread.
+                    group_end[g] = Some(spread_end(page));
+                    ready = ready
+                        .into_iter()
+                        .map(|Reverse((_, _, v))| key(v, &group_end))
+                        .collect();
+                }
+            }
+            // A unit after a weak edge can go on the same page.
+            let before_last = last.filter(|&l| l != u).map(|l| (l, 0));
+            for &(v, lag) in unit.successors.iter().chain(&before_last) {
+                available[v] = available[v].max(page + lag);
+                n_predecessors[v] -= 1;
+                if n_predecessors[v] == 0 {
+                    if available[v] <= page {
+                        ready.push(key(v, &group_end));
+                    } else {
+                        later.push(Reverse((available[v], v)));
+                    }
+                }
+            }
+        }
+        ready.extend(deferred.into_iter().map(|u| key(u, &group_end)));
+        if let Some(g) =
+            (0..n_groups).find(|&g| group_end[g] == Some(page + 1) && group_left[g] > 0)
+        {
+            return Err(Failure::Group(g, page + 1));
+        }
+        current = chapter.filter(|c| chapter_left[c] > 0);
+        // Without any unit to wait for, empty pages are a dead end after a while.
+        idle = if on_page.is_empty() && later.is_empty() {
+            idle + 1
+        } else {
+            0
+        };
+        if idle > instance.capacities.period() + 2 {
+            return Err(Failure::Stuck);
+        }
+        pages.push(on_page);
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmask::BitmaskSolver;
+    use crate::graph::DependencyGraph;
+    use crate::instance::{Pin, Spreads};
+    use crate::random::Random;
+
+    /// A random acyclic graph, with some weak edges.
+    fn random_graph(random: &mut Random, n_photos: usize) -> DependencyGraph {
+        let mut pair = || {
+            let u = 1 + random.below(n_photos as u64) as u32;
+            let v = 1 + random.below(n_photos as u64) as u32;
+            (u.min(v), u.max(v))
+        };
+        let edges: Vec<_> = (0..n_photos)
+            .map(|_| pair())
+            .filter(|(u, v)| u < v)
+            .collect();
+        let weak: Vec<_> = (0..n_photos / 3)
+            .map(|_| pair())
+            .filter(|(u, v)| u < v)
+            .collect();
+        DependencyGraph::builder(n_photos)
+            .edges(edges)
+            .weak_edges(weak)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_heuristic_against_bitmask() {
+        let mut random = Random::new(11);
+        for _ in 0..40 {
+            let n_photos = 4 + random.below(8) as usize;
+            let graph = random_graph(&mut random, n_photos);
+            for max_by_page in 2..5 {
+                let instance = Instance::new(graph.clone(), max_by_page);
+                let optimum = BitmaskSolver::new(&instance).min_pages().unwrap();
+                let schedule = super::schedule(&instance).unwrap();
+                assert!(instance.is_valid_schedule(&schedule));
+                assert!(schedule.len() >= optimum);
+                assert!(min_pages_bound(&instance) <= optimum);
+            }
+        }
+    }
+    #[test]
+    fn test_spreads_against_bitmask() {
+        let mut random = Random::new(5);
+        for _ in 0..100 {
+            let n_photos = 4 + random.below(8) as usize;
+            let graph = random_graph(&mut random, n_photos);
+            let mut spreads = Spreads::new(random.below(2) == 0);
+            for _ in 0..3 {
+                let u = 1 + random.below(n_photos as u64) as u32;
+                let v = 1 + random.below(n_photos as u64) as u32;
+                match random.below(3) {
+                    _ if u == v => (),
+                    0 => spreads.apart.push((u, v)),
+                    _ => spreads.together.push(vec![u, v]),
+                }
+            }
+            for max_by_page in 1..4 {
+                let mut instance = Instance::new(graph.clone(), max_by_page);
+                instance.spreads = Some(spreads.clone());
+                let optimum = BitmaskSolver::new(&instance).min_pages();
+                let schedule = super::schedule(&instance);
+                // The heuristic finds a schedule whenever there is one on these instances.
+                assert_eq!(schedule.is_some(), optimum.is_some());
+                if let (Some(schedule), Some(optimum)) = (schedule, optimum) {
+                    assert!(instance.is_valid_schedule(&schedule));
+                    assert!(schedule.len() >= optimum);
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_heuristic_constraints() {
+        let graph = DependencyGraph::builder(8)
+            .edges(vec![(1, 2), (2, 3)])
+            .lagged_edge(3, 4, 2)
+            .weak_edges(vec![(5, 6), (6, 5)])
+            .apart(7, 8)
+            .build()
+            .unwrap();
+        let mut instance = Instance::new(graph, 3);
+        instance.pins.insert(8, Pin::exactly(1));
+        instance.chapters.insert(5, 0);
+        instance.chapters.insert(6, 0);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        assert_eq!(schedule.len(), 5);
+        let mut spreads = Spreads::new(false);
+        spreads.together.push(vec![1, 7]);
+        spreads.apart.push((2, 8));
+        instance.spreads = Some(spreads);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        // Photos 1 and 2 share a spread, photo 1 being on the page before photo 2.
+        instance.spreads.as_mut().unwrap().together.push(vec![1, 2]);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        let mut instance = Instance::new(DependencyGraph::new(vec![(1, 2)], 2), 1);
+        let mut spreads = Spreads::new(false);
+        spreads.together.push(vec![1, 2]);
+        instance.spreads = Some(spreads.clone());
+        assert_eq!(super::schedule(&instance), Some(vec![vec![1], vec![2]]));
+        // Three photos on a spread of two pages of two photos, among many others.
+        spreads.together = vec![vec![1, 2, 3]];
+        let mut instance = Instance::new(DependencyGraph::new(vec![], 45), 2);
+        instance.spreads = Some(spreads);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        assert_eq!(schedule.len(), 23);
+        // The last photos wait for every other one, and 3 waits for 4.
+        let graph = DependencyGraph::builder(6)
+            .edge(5, 6)
+            .weak_edge(4, 3)
+            .last(1)
+            .last(4)
+            .build()
+            .unwrap();
+        let instance = Instance::new(graph, 3);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        assert_eq!(schedule.len(), 3);
+        assert_eq!(schedule[2], vec![1, 3, 4]);
+    }
+    #[test]
+    fn test_large_album() {
+        // Ten chains of fifty photos, three by page.
+        let edges =
+            (0..10).flat_map(|chain| (1..50).map(move |i| (chain * 50 + i, chain * 50 + i + 1)));
+        let graph = DependencyGraph::new(edges.collect(), 500);
+        let instance = Instance::new(graph, 3);
+        let schedule = super::schedule(&instance).unwrap();
+        assert!(instance.is_valid_schedule(&schedule));
+        assert_eq!(schedule.len(), min_pages_bound(&instance));
+    }
+}
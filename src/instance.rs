@@ -0,0 +1,454 @@
+use crate::capacity::{Capacities, Unit};
+use crate::feedback::{strongly_connected_components, FeedbackArcSet};
+use crate::graph::DependencyGraph;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A photo ordering problem: the constraints and the capacity of the pages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    pub graph: DependencyGraph,
+    pub capacities: Capacities,
+    /// Number of slots taken by each photo, `sizes[i]` being the size of photo `i + 1`.
+    pub sizes: Vec<usize>,
+    /// Pages allowed for the pinned photos.
+    pub pins: BTreeMap<u32, Pin>,
+    /// Chapter of the photos that have one: from the first page holding photos of
+    /// a chapter to the last one, no page holds a photo of another chapter (pages with
+    /// photos of no chapter only can come in between).
+    pub chapters: BTreeMap<u32, usize>,
+    /// How the pages face each other, if it matters.
+    pub spreads: Option<Spreads>,
+}
+
+/// Pages facing each other, seen together: page 1 is either a single right-hand page
+/// followed by the spreads 2-3, 4-5, ..., or on a spread with page 2.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spreads {
+    /// Page 1 is a single right-hand page.
+    pub first_alone: bool,
+    /// Groups of photos on the same spread.
+    pub together: Vec<Vec<u32>>,
+    /// Pairs of photos on different spreads.
+    pub apart: Vec<(u32, u32)>,
+}
+
+impl Spreads {
+    /// Spreads without constraints on them.
+    pub fn new(first_alone: bool) -> Self {
+        Self {
+            first_alone,
+            together: Vec::new(),
+            apart: Vec::new(),
+        }
+    }
+    /// Return the spread of the page of index `page` (from 0), numbered from 0.
+    pub fn of_page(&self, page: usize) -> usize {
+        (page + self.first_alone as usize) / 2
+    }
+    /// Return if the page of index `page` (from 0) is the last one of its spread.
+    pub fn closes(&self, page: usize) -> bool {
+        self.of_page(page) != self.of_page(page + 1)
+    }
+    /// Return if some photos are constrained by the spreads.
+    pub(crate) fn constrain(&self) -> bool {
+        !self.together.is_empty() || !self.apart.is_empty()
+    }
+    /// Return if the photos, placed on the pages of index `page_of`, meet the constraints.
+    fn respected(&self, page_of: &BTreeMap<u32, usize>) -> bool {
+        let spread = |photo: u32| page_of.get(&photo).map(|&page| self.of_page(page));
+        self.together.iter().all(|group| {
+            group
+                .windows(2)
+                .all(|pair| spread(pair[0]) == spread(pair[1]))
+        }) && self.apart.iter().all(|&(u, v)| spread(u) != spread(v))
+    }
+    /// Iterate over the photos on which there are constraints.
+    pub(crate) fn photos(&self) -> impl Iterator<Item = u32> + '_ {
+        let apart = self.apart.iter().flat_map(|&(u, v)| [u, v]);
+        self.together.iter().flatten().copied().chain(apart)
+    }
+}
+
+/// Pages on which a photo can go, numbered from 1 like in the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pin {
+    /// First page allowed.
+    pub release: usize,
+    /// Last page allowed, if any.
+    pub deadline: Option<usize>,
+}
+
+impl Default for Pin {
+    fn default() -> Self {
+        Self {
+            release: 1,
+            deadline: None,
+        }
+    }
+}
+
+impl Pin {
+    /// Exactly on page `page`.
+    pub fn exactly(page: usize) -> Self {
+        Self {
+            release: page,
+            deadline: Some(page),
+        }
+    }
+    /// On page `page` or after.
+    pub fn release(page: usize) -> Self {
+        Self {
+            release: page,
+            deadline: None,
+        }
+    }
+    /// On page `page` or before.
+    pub fn deadline(page: usize) -> Self {
+        Self {
+            release: 1,
+            deadline: Some(page),
+        }
+    }
+    /// Return the pin allowing the pages allowed by both.
+    pub fn and(self, other: Pin) -> Self {
+        let deadline = match (self.deadline, other.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Self {
+            release: self.release.max(other.release),
+            deadline,
+        }
+    }
+    /// Return if at least one page is allowed.
+    pub fn is_valid(&self) -> bool {
+        self.release > 0
+            && self
+                .deadline
+                .is_none_or(|deadline| deadline >= self.release)
+    }
+    /// Return if the page of index `page` (from 0) is allowed.
+    pub(crate) fn allows(&self, page: usize) -> bool {
+        self.release <= page + 1 && self.deadline.is_none_or(|deadline| page < deadline)
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.deadline {
+            Some(deadline) if deadline == self.release => write!(f, "page {}", deadline),
+            Some(deadline) => write!(f, "pages {} to {}", self.release, deadline),
+            None => write!(f, "page {} or after", self.release),
+        }
+    }
+}
+
+/// An assignment of photos to pages, one entry per page.
+pub type Schedule = Vec<Vec<u32>>;
+
+/// Photos held back by the lag of an edge, sorted, with the number of pages
+/// they still have to wait before the next page can hold them.
+pub(crate) type Waits = Vec<(u32, usize)>;
+
+impl Instance {
+    /// An instance where every page holds `max_by_page` photos.
+    pub fn new(graph: DependencyGraph, max_by_page: usize) -> Self {
+        Self::with_capacities(graph, Capacities::uniform(max_by_page))
+    }
+    /// An instance where the pages hold a varying number of photos.
+    pub fn with_capacities(graph: DependencyGraph, capacities: Capacities) -> Self {
+        let sizes = vec![1; graph.count_vertices()];
+        Self {
+            graph,
+            capacities,
+            sizes,
+            pins: BTreeMap::new(),
+            chapters: BTreeMap::new(),
+            spreads: None,
+        }
+    }
+    /// Return the number of slots taken by a photo.
+    pub fn size(&self, photo: u32) -> usize {
+        self.sizes[photo as usize - 1]
+    }
+    /// Return the pages allowed for a photo.
+    pub fn pin(&self, photo: u32) -> Pin {
+        self.pins.get(&photo).copied().unwrap_or_default()
+    }
+    /// Return the chapter of a photo, if any.
+    pub fn chapter(&self, photo: u32) -> Option<usize> {
+        self.chapters.get(&photo).copied()
+    }
+    /// Return the chapters whose photos can go on the next page, `None` standing
+    /// for the photos without chapter only: the chapter `current` still open,
+    /// or else any chapter not `started` yet.
+    pub(crate) fn next_chapters(
+        &self,
+        current: Option<usize>,
+        started: impl Fn(usize) -> bool,
+    ) -> Vec<Option<usize>> {
+        match current {
+            Some(chapter) => vec![Some(chapter)],
+            None => {
+                let chapters: BTreeSet<usize> = self.chapters.values().copied().collect();
+                let fresh = chapters.into_iter().filter(|&chapter| !started(chapter));
+                std::iter::once(None).chain(fresh.map(Some)).collect()
+            }
+        }
+    }
+    /// Return the photos of each chapter if they can be solved one by one and
+    /// their schedules concatenated: every photo has a chapter, no edge goes
+    /// from a chapter to another, no photo is pinned and the pages all hold the same.
+    pub(crate) fn independent_chapters(&self) -> Option<Vec<Vec<u32>>> {
+        let n_photos = self.graph.count_vertices();
+        let independent = !self.chapters.is_empty()
+            && self.chapters.len() == n_photos
+            && self.pins.is_empty()
+            && self.graph.last.is_empty()
+            && self.spreads.is_none()
+            && self.capacities.as_uniform().is_some()
+            && self
+                .graph
+                .edges()
+                .chain(self.graph.weak_edges())
+                .all(|(u, v)| self.chapter(u) == self.chapter(v));
+        if !independent {
+            return None;
+        }
+        let mut chapters: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+        for (&photo, &chapter) in &self.chapters {
+            chapters.entry(chapter).or_default().push(photo);
+        }
+        Some(chapters.into_values().collect())
+    }
+    /// Return the instance on the (sorted) `photos` only, photo `photos[i]`
+    /// being renumbered `i + 1`, without chapters.
+    pub(crate) fn restricted(&self, photos: &[u32]) -> Instance {
+        let number = |photo: u32| photos.binary_search(&photo).ok().map(|i| i as u32 + 1);
+        let both = |(u, v): (u32, u32)| Some((number(u)?, number(v)?));
+        let edges = self.graph.edges().filter_map(|(u, v)| {
+            let (x, y) = both((u, v))?;
+            Some((x, y, self.graph.lag(u, v)))
+        });
+        let mut builder = DependencyGraph::builder(photos.len())
+            .lagged_edges(edges)
+            .weak_edges(self.graph.weak_edges().filter_map(both));
+        for (u, v) in self.graph.apart_pairs().filter_map(both) {
+            builder = builder.apart(u, v);
+        }
+        for photo in self.graph.last_photos().filter_map(number) {
+            builder = builder.last(photo);
+        }
+        let mut instance =
+            Instance::with_capacities(builder.build().unwrap(), self.capacities.clone());
+        instance.sizes = photos.iter().map(|&photo| self.size(photo)).collect();
+        instance.pins = self
+            .pins
+            .iter()
+            .filter_map(|(&photo, &pin)| Some((number(photo)?, pin)))
+            .collect();
+        instance
+    }
+    /// Return the number of pages from the first one whose pins matter:
+    /// from there on, no photo waits for its release and no deadline is ahead.
+    pub(crate) fn pinned_pages(&self) -> usize {
+        self.pins
+            .values()
+            .map(|pin| pin.deadline.unwrap_or(0).max(pin.release))
+            .max()
+            .unwrap_or(0)
+    }
+    /// Return if a photo not placed before page `page` (from 0) can still meet its deadline.
+    pub(crate) fn before_deadline(&self, photo: u32, page: usize) -> bool {
+        self.pin(photo)
+            .deadline
+            .is_none_or(|deadline| page < deadline)
+    }
+    /// Return if every page holds a single photo and no page has to stay empty
+    /// for a lag nor chapters or spread constraints are given, so that the photos can go one by one in a topological order.
+    pub(crate) fn one_by_page(&self) -> bool {
+        self.capacities.as_uniform() == Some(1)
+            && self.pins.is_empty()
+            && self.graph.lags.is_empty()
+            && self.chapters.is_empty()
+            && !self.spread_constrained()
+    }
+    /// Return if some photos are constrained by the spreads.
+    pub(crate) fn spread_constrained(&self) -> bool {
+        self.spreads.as_ref().is_some_and(Spreads::constrain)
+    }
+    /// Return if the page of index `page` (from 0) is the last one of its spread,
+    /// every page being its own spread without a spread model.
+    pub(crate) fn closes_spread(&self, page: usize) -> bool {
+        self.spreads
+            .as_ref()
+            .is_none_or(|spreads| spreads.closes(page))
+    }
+    /// Return the waits for the page after the one holding `photos`,
+    /// given the `waits` for that page.
+    pub(crate) fn next_waits(&self, waits: &[(u32, usize)], photos: &[u32]) -> Waits {
+        let mut next: BTreeMap<u32, usize> = waits
+            .iter()
+            .filter(|&&(_, wait)| wait > 1)
+            .map(|&(photo, wait)| (photo, wait - 1))
+            .collect();
+        for (u, v, lag) in self.graph.lagged_edges() {
+            if photos.contains(&u) {
+                let wait = next.entry(v).or_insert(0);
+                *wait = (*wait).max(lag - 1);
+            }
+        }
+        next.into_iter().collect()
+    }
+    /// Return the number of slots taken by a set of photos.
+    pub fn page_size(&self, photos: &[u32]) -> usize {
+        photos.iter().map(|&photo| self.size(photo)).sum()
+    }
+    /// Split photos that can go on the next page into units, the photos linked
+    /// by a cycle of weak edges forming a single unit, in an order compatible
+    /// with the weak edges between them (apart photos must be in different units).
+    /// The photos on the last page form a unit requiring all the others.
+    pub(crate) fn page_units(&self, photos: &[u32]) -> Vec<Unit> {
+        let mut weak_adj_list: BTreeMap<u32, Vec<u32>> = photos
+            .iter()
+            .map(|&u| {
+                let neighbourhood = self.graph.weak_adj_list[&u]
+                    .iter()
+                    .copied()
+                    .filter(|v| photos.binary_search(v).is_ok())
+                    .collect();
+                (u, neighbourhood)
+            })
+            .collect();
+        // Photos with a weak edge to a last photo share its page, as seen through
+        // the end of the album (photo 0).
+        let last: Vec<u32> = self
+            .graph
+            .last_photos()
+            .filter(|v| photos.binary_search(v).is_ok())
+            .collect();
+        if !last.is_empty() {
+            for neighbourhood in weak_adj_list.values_mut() {
+                neighbourhood.push(0);
+            }
+        }
+        let mut with_end = weak_adj_list.clone();
+        with_end.insert(0, last.clone());
+        // The components come after the ones they have edges to.
+        let mut components = strongly_connected_components(&with_end);
+        components.reverse();
+        for component in &mut components {
+            component.retain(|&photo| photo != 0);
+        }
+        components.retain(|component| !component.is_empty());
+        let mut unit_of = BTreeMap::new();
+        for (i, component) in components.iter().enumerate() {
+            for &photo in component {
+                unit_of.insert(photo, i);
+            }
+        }
+        let mut units: Vec<Unit> = components
+            .into_iter()
+            .map(|photos| Unit {
+                size: self.page_size(&photos),
+                photos,
+                requires: Vec::new(),
+                conflicts: Vec::new(),
+            })
+            .collect();
+        for (u, neighbourhood) in &weak_adj_list {
+            for v in neighbourhood.iter().filter(|&&v| v != 0) {
+                let (i, j) = (unit_of[u], unit_of[v]);
+                if i != j && !units[j].requires.contains(&i) {
+                    units[j].requires.push(i);
+                }
+            }
+        }
+        if let Some(&v) = last.first() {
+            let j = unit_of[&v];
+            units[j].requires = (0..units.len()).filter(|&i| i != j).collect();
+        }
+        // Photos on different spreads are on different pages too.
+        let spread_apart = self
+            .spreads
+            .iter()
+            .flat_map(|spreads| spreads.apart.iter().copied());
+        for (u, v) in self.graph.apart_pairs().chain(spread_apart) {
+            if let (Some(&i), Some(&j)) = (unit_of.get(&u), unit_of.get(&v)) {
+                if !units[i].conflicts.contains(&j) {
+                    units[i].conflicts.push(j);
+                    units[j].conflicts.push(i);
+                }
+            }
+        }
+        units
+    }
+    /// Return a feasible instance obtained by dropping a small set of edges,
+    /// given with it (of minimum size when `exact`).
+    pub fn repaired(&self) -> (Instance, FeedbackArcSet) {
+        let dropped = self.graph.feedback_arc_set();
+        let mut repaired = self.clone();
+        repaired.graph = self.graph.without_edges(&dropped.edges);
+        (repaired, dropped)
+    }
+    /// Check that `schedule` places every photo once on a page allowed by its pin,
+    /// respects the edges with their lag, the apart pairs, the last photos, the chapters
+    /// and the spreads, and does not exceed
+    /// the capacity of the pages with the size of its photos.
+    pub fn is_valid_schedule(&self, schedule: &[Vec<u32>]) -> bool {
+        let n_photos = self.graph.count_vertices();
+        let mut page_of = BTreeMap::new();
+        for (i, page) in schedule.iter().enumerate() {
+            for &photo in page {
+                if photo == 0 || photo as usize > n_photos || page_of.insert(photo, i).is_some() {
+                    return false;
+                }
+                if !self.pin(photo).allows(i) {
+                    return false;
+                }
+            }
+            if self.page_size(page) > self.capacities.of_page(i) {
+                return false;
+            }
+        }
+        // The chapters in the order of the pages, each one once.
+        let mut chapters = Vec::new();
+        for page in schedule {
+            let on_page: BTreeSet<usize> = page
+                .iter()
+                .filter_map(|&photo| self.chapter(photo))
+                .collect();
+            if on_page.len() > 1 {
+                return false;
+            }
+            for chapter in on_page {
+                if chapters.last() != Some(&chapter) {
+                    if chapters.contains(&chapter) {
+                        return false;
+                    }
+                    chapters.push(chapter);
+                }
+            }
+        }
+        let end = page_of.values().max().copied();
+        page_of.len() == n_photos
+            && self.graph.last_photos().all(|v| Some(page_of[&v]) == end)
+            && self
+                .graph
+                .edges()
+                .all(|(u, v)| page_of[&u] + self.graph.lag(u, v) <= page_of[&v])
+            && self
+                .graph
+                .weak_edges()
+                .all(|(u, v)| page_of[&u] <= page_of[&v])
+            && self
+                .graph
+                .apart_pairs()
+                .all(|(u, v)| page_of[&u] != page_of[&v])
+            && self
+                .spreads
+                .as_ref()
+                .is_none_or(|spreads| spreads.respected(&page_of))
+    }
+}
@@ -0,0 +1,138 @@
+//! JSON output of the command line tool, written by hand as the values are few:
+//! numbers, photos, schedules and messages.
+
+use crate::error::SolveError;
+use crate::instance::Schedule;
+use itertools::Itertools;
+use std::fmt::{self, Write};
+
+/// A JSON value, its strings being escaped when printed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    /// Fields printed in order.
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    /// A string holding the text of a value, such as an error.
+    pub fn text(text: impl fmt::Display) -> Json {
+        Json::String(text.to_string())
+    }
+    /// An array of photos.
+    pub fn photos(photos: &[u32]) -> Json {
+        Json::Array(
+            photos
+                .iter()
+                .map(|&photo| Json::Number(photo as usize))
+                .collect(),
+        )
+    }
+    /// A schedule as an array of pages.
+    pub fn pages(schedule: &Schedule) -> Json {
+        Json::Array(schedule.iter().map(|page| Json::photos(page)).collect())
+    }
+    /// A list of edges as an array of pairs.
+    pub fn edges(edges: &[(u32, u32)]) -> Json {
+        Json::Array(edges.iter().map(|&(u, v)| Json::photos(&[u, v])).collect())
+    }
+    /// Describe why an instance is impossible: a cycle with its edges, for the editing
+    /// tools to highlight, or photos that must share a page with the chain separating them.
+    /// Other errors have no such fields.
+    pub fn witness(error: &SolveError) -> Vec<(&'static str, Json)> {
+        match error {
+            SolveError::Cyclic { cycle } => {
+                let edges: Vec<_> = cycle
+                    .iter()
+                    .copied()
+                    .zip(cycle.iter().copied().cycle().skip(1))
+                    .collect();
+                vec![
+                    ("cycle", Json::photos(cycle)),
+                    ("edges", Json::edges(&edges)),
+                ]
+            }
+            SolveError::SeparatedGroup { photos, chain } => {
+                let edges: Vec<_> = chain.iter().copied().tuple_windows().collect();
+                vec![
+                    ("group", Json::photos(photos)),
+                    ("chain", Json::photos(chain)),
+                    ("edges", Json::edges(&edges)),
+                ]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => write!(f, "[{}]", values.iter().format(", ")),
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ": {}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Write a JSON string, escaping the quotes, the backslashes and the control characters.
+fn write_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let json = Json::Object(vec![
+            ("error", Json::text("a \"quoted\" C:\\ path\n\u{1}")),
+            ("pages", Json::pages(&vec![vec![1, 2], vec![]])),
+            ("max_by_page", Json::Null),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"error": "a \"quoted\" C:\\ path\n\u0001", "pages": [[1, 2], []], "max_by_page": null}"#
+        );
+    }
+    #[test]
+    fn test_witness() {
+        let cycle = SolveError::Cyclic {
+            cycle: vec![1, 3, 2],
+        };
+        let fields = Json::Object(Json::witness(&cycle));
+        assert_eq!(
+            fields.to_string(),
+            r#"{"cycle": [1, 3, 2], "edges": [[1, 3], [3, 2], [2, 1]]}"#
+        );
+        assert!(Json::witness(&SolveError::Stopped).is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+//! Minimum number of pages of a photo album with ordering constraints.
+//!
+//! Photos are numbered from 1 and each page holds a bounded number of photos,
+//! either the same for every page or following a sequence of `Capacities`.
+//! An edge `(u, v)` of the `DependencyGraph` means that photo `u` must be on
+//! a page strictly before photo `v`, and a weak edge that it is on the same page
+//! or before. Edges can also require a lag of several pages between their photos.
+//! Facing pages form `Spreads`, and photos can be required on the same spread or apart.
+
+mod bitmask;
+mod bound;
+mod capacity;
+mod count;
+mod error;
+mod feedback;
+mod graph;
+mod heuristic;
+mod instance;
+mod json;
+mod parse;
+mod random;
+#[cfg(test)]
+mod reference;
+mod search;
+mod solver;
+mod special;
+mod stop;
+
+pub use bitmask::Schedules;
+pub use capacity::Capacities;
+pub use count::Count;
+pub use error::{GraphError, Location, ParseError, SolveError};
+pub use feedback::FeedbackArcSet;
+pub use graph::{DependencyGraph, DependencyGraphBuilder};
+pub use instance::{Instance, Pin, Schedule, Spreads};
+pub use json::Json;
+pub use parse::{parse, read_file, Parsed, Parser};
+pub use solver::{Method, Solution, Solver, Statistics};
+pub use stop::Cancel;
@@ -1,227 +1,534 @@
 use itertools::Itertools;
-use std::cmp::{max, min};
-use std::collections::{BTreeMap, BTreeSet};
+use photo_ordering::{Instance, Json, Method, Parser, Schedule, SolveError, Solver};
 use std::env;
-use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fmt;
+use std::process;
+use std::time::Duration;
 
-fn main() {
-    let filename = env::args().nth(1).expect("No input found");
-    let (graph, max_by_page) = read_file(&filename).unwrap();
-    match min_pages(graph, max_by_page) {
-        Some(n_pages) => println!("{}", n_pages),
-        None => println!("Impossible"),
-    }
+/// Exit code when the instance cannot be solved (other than being impossible).
+const EXIT_SOLVE_ERROR: i32 = 1;
+/// Exit code when the input file cannot be read.
+const EXIT_PARSE_ERROR: i32 = 2;
+/// Number of photos above which the heuristic gives the number of pages and the schedule,
+/// unless `--exact` is given.
+const HEURISTIC_ABOVE: usize = 40;
+/// How to call the program, printed on a wrong command line.
+const USAGE: &str = "usage: photo-ordering [options] <input file> (see the README for the options)";
+
+/// Command line options.
+struct Options {
+    filename: String,
+    print_schedule: bool,
+    json: bool,
+    method: Method,
+    lenient: bool,
+    page_multiple: usize,
+    /// Print every optimal schedule, or at most this many.
+    all: Option<Option<usize>>,
+    /// Count the optimal schedules.
+    count: bool,
+    /// Count the schedules within this many pages.
+    count_within: Option<usize>,
+    /// Print an optimal schedule drawn at random with this seed.
+    sample: Option<u64>,
+    /// Numbers of photos by page to solve the instance for, all of them from 1 to `n` if empty.
+    sweep: Option<Vec<usize>>,
+    /// Find the fewest photos by page fitting on this many pages.
+    fit: Option<usize>,
+    /// Print the counters of the search.
+    stats: bool,
+    /// Never switch to the heuristic for large albums.
+    exact: bool,
+    /// Stop the search after this long, keeping the best schedule found.
+    time_limit: Option<Duration>,
+    /// Stop the search after this many nodes, keeping the best schedule found.
+    node_budget: Option<u64>,
 }
 
-/// Compute the minimum number of pages given the image graph and the max on each page.
-fn min_pages(graph: DependencyGraph, max_by_page: usize) -> Option<usize> {
-    assert!(max_by_page > 0);
-    if !graph.is_acyclic() {
-        None
-    } else {
-        Some(min_pages_feasible(graph, max_by_page))
+impl Options {
+    /// Whether the search may stop before proving its schedule optimal.
+    fn limited(&self) -> bool {
+        self.time_limit.is_some() || self.node_budget.is_some()
+    }
+    /// Whether only the number of pages and the schedule are asked for, without a page multiple.
+    fn plain(&self) -> bool {
+        self.all.is_none()
+            && !self.count
+            && self.count_within.is_none()
+            && self.sample.is_none()
+            && self.sweep.is_none()
+            && self.fit.is_none()
+            && self.page_multiple == 1
     }
 }
 
-/// Return the minimum number of pages assuming the problem is feasible.
-fn min_pages_feasible(mut graph: DependencyGraph, max_by_page: usize) -> usize {
-    let n_photos = graph.count_vertices();
-    if n_photos == 0 {
-        return 0;
+fn parse_args() -> Options {
+    let mut options = Options {
+        filename: String::new(),
+        print_schedule: false,
+        json: false,
+        method: Method::Bitmask,
+        lenient: false,
+        page_multiple: 1,
+        all: None,
+        count: false,
+        count_within: None,
+        sample: None,
+        sweep: None,
+        fit: None,
+        stats: false,
+        exact: false,
+        time_limit: None,
+        node_budget: None,
     };
-    if max_by_page == 1 {
-        return n_photos;
-    }
-    // Get the photos that can go anywhere
-    let photos_no_dependency = graph.isolated_vertices();
-    // Case 1: Photos without dependency can be added anywhere afterwards
-    // as long as there are ceil(n_photos / max_by_page) spots.
-    if !photos_no_dependency.is_empty() {
-        for &photo in &photos_no_dependency {
-            graph.remove(photo);
-        }
-        return max(
-            (n_photos + max_by_page - 1) / max_by_page,
-            min_pages_feasible(graph, max_by_page),
+    let mut filename = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schedule" => options.print_schedule = true,
+            "--json" => options.json = true,
+            "--reference" => options.method = Method::Reference,
+            "--heuristic" => options.method = Method::Heuristic,
+            "--exact" => options.exact = true,
+            "--lenient" => options.lenient = true,
+            "--stats" => options.stats = true,
+            "--page-multiple" => {
+                options.page_multiple =
+                    args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                        eprintln!("error: --page-multiple needs a number of pages");
+                        process::exit(EXIT_PARSE_ERROR)
+                    })
+            }
+            "--time-limit" => {
+                let seconds = args.next().and_then(|s| s.parse().ok());
+                options.time_limit = Some(
+                    seconds
+                        .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                        .unwrap_or_else(|| {
+                            eprintln!("error: --time-limit needs a number of seconds");
+                            process::exit(EXIT_PARSE_ERROR)
+                        }),
+                )
+            }
+            "--node-budget" => {
+                options.node_budget =
+                    Some(args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                        eprintln!("error: --node-budget needs a number of nodes");
+                        process::exit(EXIT_PARSE_ERROR)
+                    }))
+            }
+            "--all" => options.all = Some(options.all.flatten()),
+            "--limit" => {
+                let limit = args.next().and_then(|k| k.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("error: --limit needs a number of schedules");
+                    process::exit(EXIT_PARSE_ERROR)
+                });
+                options.all = Some(Some(limit))
+            }
+            "--count" => options.count = true,
+            "--count-within" => {
+                options.count_within =
+                    Some(args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                        eprintln!("error: --count-within needs a number of pages");
+                        process::exit(EXIT_PARSE_ERROR)
+                    }))
+            }
+            "--sample" => {
+                options.sample =
+                    Some(args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                        eprintln!("error: --sample needs a seed");
+                        process::exit(EXIT_PARSE_ERROR)
+                    }));
+                options.print_schedule = true
+            }
+            "--sweep" => {
+                let list = args.next().unwrap_or_default();
+                let numbers = if list == "all" {
+                    Some(Vec::new())
+                } else {
+                    list.split(',').map(|n| n.parse().ok()).collect()
+                };
+                options.sweep = Some(numbers.unwrap_or_else(|| {
+                    eprintln!(
+                        "error: --sweep needs `all` or numbers of photos by page, such as 2,3,4"
+                    );
+                    process::exit(EXIT_PARSE_ERROR)
+                }))
+            }
+            "--fit" => {
+                options.fit = Some(args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("error: --fit needs a number of pages");
+                    process::exit(EXIT_PARSE_ERROR)
+                }))
+            }
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ if filename.is_some() => usage_error(&format!("a single input is read, not {}", arg)),
+            _ => filename = Some(arg),
+        }
+    }
+    options.filename = filename.unwrap_or_else(|| usage_error("no input file given"));
+    let enumerates = options.all.is_some()
+        || options.count
+        || options.count_within.is_some()
+        || options.sample.is_some();
+    if options.exact && options.method == Method::Heuristic {
+        eprintln!("error: --exact cannot be used with --heuristic");
+        process::exit(EXIT_PARSE_ERROR)
+    }
+    if enumerates && options.page_multiple > 1 {
+        eprintln!(
+            "error: --all, --limit, --count and --sample cannot be used with --page-multiple"
         );
+        process::exit(EXIT_PARSE_ERROR)
     }
-    // Get the photos that can go on the next page
-    let photos_ready = graph.roots();
-    // Case 2: All ready-to-use photos fit in the next page.
-    if photos_ready.len() <= max_by_page {
-        for &photo in &photos_ready {
-            graph.remove(photo);
-        }
-        return 1 + min_pages_feasible(graph, max_by_page);
+    if options.limited() && !options.plain() {
+        eprintln!(
+            "error: --time-limit and --node-budget only apply to the number of pages and the schedule, without --page-multiple"
+        );
+        process::exit(EXIT_PARSE_ERROR)
     }
-    // Case 3: Try all max_by_page-combination for the next page.
-    let mut result = n_photos;
-    for page in photos_ready.iter().combinations(max_by_page) {
-        let mut subgraph = graph.clone();
-        for &photo in page {
-            subgraph.remove(photo);
-        }
-        result = min(result, 1 + min_pages_feasible(subgraph, max_by_page));
+    // The counts, sweeps and fits need the optimum, which the heuristic does not give.
+    if options.method == Method::Heuristic && !options.plain() {
+        eprintln!(
+            "error: --heuristic only applies to the number of pages and the schedule, without --page-multiple"
+        );
+        process::exit(EXIT_PARSE_ERROR)
     }
-    result
+    options
 }
 
-/// Directed graph data structure by adjacency lists
-#[derive(Clone, Debug, PartialEq, Eq)]
-struct DependencyGraph {
-    adj_list: BTreeMap<u32, Vec<u32>>,
+/// Print an error on the command line with the usage, and exit.
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    eprintln!("{}", USAGE);
+    process::exit(EXIT_PARSE_ERROR)
 }
 
-impl DependencyGraph {
-    fn new(edges: Vec<(u32, u32)>, n_vertices: usize) -> Self {
-        let mut adj_list = BTreeMap::new();
-        for v in 1..=n_vertices as u32 {
-            adj_list.insert(v, Vec::new());
-        }
-        for (u, v) in &edges {
-            adj_list.get_mut(u).unwrap().push(*v)
-        }
-        Self { adj_list }
-    }
-    /// Return the set of vertices with no ingoing edge.
-    fn roots(&self) -> BTreeSet<u32> {
-        let mut result: BTreeSet<u32> = self.adj_list.keys().copied().collect();
-        for neighbourhood in self.adj_list.values() {
-            for u in neighbourhood.iter() {
-                result.remove(u);
-            }
-        }
-        result
-    }
-    /// Return the set of vertices with no edge (in- or outgoig)
-    fn isolated_vertices(&self) -> Vec<u32> {
-        self.roots()
-            .into_iter()
-            .filter(|v| self.adj_list.get(v).unwrap().is_empty())
-            .collect()
-    }
-    /// Remove a vertex.
-    fn remove(&mut self, vertex: u32) {
-        self.adj_list.remove(&vertex);
-    }
-    fn count_vertices(&self) -> usize {
-        self.adj_list.len()
-    }
-    /// Compute if the graph contains no directed cycle.
-    // Note: this is slower than a bfs because get_roots is not optimized.
-    fn is_acyclic(&self) -> bool {
-        let mut graph = self.clone();
-        while !graph.adj_list.is_empty() {
-            let roots = graph.roots();
-            if roots.is_empty() {
-                return false;
+fn main() {
+    let mut options = parse_args();
+    let parser = Parser::new().lenient(options.lenient);
+    let parsed = parser.read_file(&options.filename).unwrap_or_else(|error| {
+        eprintln!("error: {}", error);
+        process::exit(EXIT_PARSE_ERROR)
+    });
+    for warning in &parsed.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let instance = parsed.instance;
+    if options.method == Method::Bitmask
+        && !options.exact
+        && options.plain()
+        && instance.graph.count_vertices() > HEURISTIC_ABOVE
+    {
+        // Within limits, the branch and bound improves on the heuristic until it stops.
+        options.method = if options.limited() {
+            Method::Reference
+        } else {
+            Method::Heuristic
+        };
+    }
+    match solve(&instance, &options) {
+        Ok(output) => println!("{}", output),
+        Err(error @ (SolveError::Cyclic { .. } | SolveError::SeparatedGroup { .. })) => {
+            // Suggest constraints to drop and solve without them.
+            let (repaired, dropped) = instance.repaired();
+            let output = solve(&repaired, &options).unwrap_or_else(|error| exit_with(error));
+            if let Output::Json(repaired) = output {
+                let mut fields = vec![("status", Json::text("impossible"))];
+                fields.extend(Json::witness(&error));
+                fields.extend([
+                    ("drop", Json::edges(&dropped.edges)),
+                    ("drop_minimum", Json::Bool(dropped.exact)),
+                    ("repaired", repaired),
+                ]);
+                println!("{}", Json::Object(fields));
             } else {
-                for &u in &roots {
-                    graph.remove(u)
+                println!("Impossible");
+                match error {
+                    SolveError::Cyclic { cycle } => {
+                        println!("Cycle: {} -> {}", cycle.iter().join(" -> "), cycle[0])
+                    }
+                    SolveError::SeparatedGroup { photos, chain } => {
+                        println!("Same page: {}", photos.iter().join(" "));
+                        println!("Separated by: {}", chain.iter().join(" -> "));
+                    }
+                    _ => unreachable!(),
                 }
+                let minimum = if dropped.exact {
+                    "minimum"
+                } else {
+                    "heuristic"
+                };
+                println!("Constraints to drop ({}):", minimum);
+                for (u, v) in &dropped.edges {
+                    println!("  {} {}", u, v);
+                }
+                println!("Without them:");
+                println!("{}", output);
             }
         }
-        true
+        Err(error) => exit_with(error),
     }
 }
 
-// Reading input file
-fn read_file(filename: &str) -> Result<(DependencyGraph, usize), Box<dyn Error>> {
-    let mut lines = BufReader::new(File::open(filename)?).lines();
-    let first_line: Vec<usize> = lines
-        .next()
-        .unwrap()?
-        .split(' ')
-        .map(|s| s.parse::<usize>().unwrap())
-        .collect();
-    let n = first_line[0];
-    let m = first_line[1];
-    let _k = first_line[2];
-    let mut edges: Vec<(u32, u32)> = Vec::new();
-    for line in lines {
-        let parsed: Vec<_> = line?.split(' ').map(|s| s.parse().unwrap()).collect();
-        if !parsed.is_empty() {
-            edges.push((parsed[0], parsed[1]));
-        }
-    }
-    Ok((DependencyGraph::new(edges, n), m))
+/// Solve a feasible instance and format the result,
+/// printing the counters of the search on the error output if asked.
+fn solve(instance: &Instance, options: &Options) -> Result<Output, SolveError> {
+    let mut solver = Solver::new(instance)
+        .method(options.method)
+        .page_multiple(options.page_multiple);
+    if let Some(limit) = options.time_limit {
+        solver = solver.time_limit(limit);
+    }
+    if let Some(nodes) = options.node_budget {
+        solver = solver.node_budget(nodes);
+    }
+    let output = report(instance, &solver, options);
+    if options.stats {
+        let statistics = solver.statistics();
+        eprintln!(
+            "Search: {} nodes, {} pruned",
+            statistics.nodes, statistics.pruned
+        );
+    }
+    output
 }
 
-// Unit tests
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Run the computation asked by the options and format its result.
+fn report(instance: &Instance, solver: &Solver, options: &Options) -> Result<Output, SolveError> {
+    if let Some(limit) = options.all {
+        // There is always a first schedule, which gives the number of pages.
+        let mut schedules = solver.schedules()?.peekable();
+        let n_pages = schedules.peek().map_or(0, Vec::len);
+        let schedules: Vec<_> = schedules.take(limit.unwrap_or(usize::MAX)).collect();
+        if options.json {
+            return Ok(Output::Json(Json::Object(vec![
+                ("status", Json::text("optimal")),
+                ("pages", Json::Number(n_pages)),
+                (
+                    "schedules",
+                    Json::Array(schedules.iter().map(Json::pages).collect()),
+                ),
+            ])));
+        }
+        let mut lines = vec![n_pages.to_string()];
+        for (i, schedule) in schedules.iter().enumerate() {
+            lines.push(format!("Schedule {}:", i + 1));
+            lines.extend(page_lines(instance, schedule));
+        }
+        return Ok(Output::Text(lines.join("\n")));
+    }
+    if let Some(max_by_page) = &options.sweep {
+        let max_by_page = if max_by_page.is_empty() {
+            (1..=instance.graph.count_vertices().max(1)).collect()
+        } else {
+            max_by_page.clone()
+        };
+        let results = solver.sweep(&max_by_page);
+        // The constraints themselves are impossible: report it as for a single capacity.
+        let impossible = results.iter().find_map(|result| match result {
+            Err(error @ (SolveError::Cyclic { .. } | SolveError::SeparatedGroup { .. })) => {
+                Some(error.clone())
+            }
+            _ => None,
+        });
+        if let Some(error) = impossible {
+            return Err(error);
+        }
+        if options.json {
+            let rows = max_by_page.iter().zip(&results).map(|(&max, result)| {
+                let result = match result {
+                    Ok(n_pages) => ("pages", Json::Number(*n_pages)),
+                    Err(error) => ("error", Json::text(error)),
+                };
+                Json::Object(vec![("max_by_page", Json::Number(max)), result])
+            });
+            return Ok(Output::Json(Json::Object(vec![
+                ("status", Json::text("sweep")),
+                ("sweep", Json::Array(rows.collect())),
+            ])));
+        }
+        let mut lines = vec!["By page  Pages".to_string()];
+        for (max, result) in max_by_page.iter().zip(&results) {
+            match result {
+                Ok(n_pages) => lines.push(format!("{:>7}  {:>5}", max, n_pages)),
+                Err(error) => lines.push(format!("{:>7}  error: {}", max, error)),
+            }
+        }
+        return Ok(Output::Text(lines.join("\n")));
+    }
+    if let Some(n_pages) = options.fit {
+        let max_by_page = solver.min_capacity(n_pages)?;
+        if options.json {
+            return Ok(Output::Json(Json::Object(vec![
+                ("status", Json::text("fit")),
+                ("pages", Json::Number(n_pages)),
+                ("max_by_page", max_by_page.map_or(Json::Null, Json::Number)),
+            ])));
+        }
+        return Ok(Output::Text(match max_by_page {
+            Some(max) => format!("Fits on {} pages with {} by page", n_pages, max),
+            None => format!("Does not fit on {} pages", n_pages),
+        }));
+    }
+    if options.count || options.count_within.is_some() {
+        let n_pages = solver.min_pages()?;
+        let optimal = if options.count {
+            Some(solver.count_optimal()?)
+        } else {
+            None
+        };
+        let within = match options.count_within {
+            Some(within) => Some((within, solver.count_within(within)?)),
+            None => None,
+        };
+        if options.json {
+            // The counts are strings, as they can exceed the integers of JSON parsers.
+            let mut fields = vec![
+                ("status", Json::text("optimal")),
+                ("pages", Json::Number(n_pages)),
+            ];
+            if let Some(count) = optimal {
+                fields.push(("count", Json::text(count)));
+            }
+            if let Some((within, count)) = within {
+                let within = vec![
+                    ("pages", Json::Number(within)),
+                    ("count", Json::text(count)),
+                ];
+                fields.push(("count_within", Json::Object(within)));
+            }
+            return Ok(Output::Json(Json::Object(fields)));
+        }
+        let mut lines = vec![n_pages.to_string()];
+        if let Some(count) = optimal {
+            lines.push(format!("Optimal schedules: {}", count));
+        }
+        if let Some((within, count)) = within {
+            lines.push(format!("Schedules within {} pages: {}", within, count));
+        }
+        return Ok(Output::Text(lines.join("\n")));
+    }
+    // With a page multiple, tell how full the pages are once the photos are spread.
+    let spread = options.page_multiple > 1;
+    // A search within limits may stop before proving its schedule optimal.
+    let solution = if options.limited() {
+        Some(solver.solve()?)
+    } else {
+        None
+    };
+    // A heuristic or stopped result comes with a lower bound,
+    // to see how far it can be from the optimum.
+    let lower_bound = match (&solution, options.method) {
+        (Some(solution), _) => Some(solution.lower_bound).filter(|_| !solution.optimal),
+        (None, Method::Heuristic) => Some(solver.lower_bound()?),
+        _ => None,
+    };
+    let schedule = || match (&solution, options.sample) {
+        (Some(solution), _) => Ok(solution.schedule.clone()),
+        (None, Some(seed)) => solver.sample(seed),
+        (None, None) => solver.schedule(),
+    };
+    if options.json {
+        let schedule = schedule()?;
+        let status = match lower_bound {
+            None => "optimal",
+            Some(_) if options.method == Method::Heuristic => "heuristic",
+            Some(_) => "stopped",
+        };
+        let mut fields = vec![
+            ("status", Json::text(status)),
+            ("pages", Json::Number(schedule.len())),
+            ("schedule", Json::pages(&schedule)),
+        ];
+        if let Some(bound) = lower_bound {
+            fields.push(("lower_bound", Json::Number(bound)));
+        }
+        if spread {
+            let max = max_by_page(instance, &schedule);
+            fields.push(("max_by_page", Json::Number(max)));
+        }
+        if let Some(spreads) = &instance.spreads {
+            // The pages of each spread, numbered from 1.
+            let pages = (0..schedule.len())
+                .group_by(|&page| spreads.of_page(page))
+                .into_iter()
+                .map(|(_, pages)| {
+                    let pages: Vec<_> = pages.map(|page| page as u32 + 1).collect();
+                    Json::photos(&pages)
+                })
+                .collect();
+            fields.push(("spreads", Json::Array(pages)));
+        }
+        Ok(Output::Json(Json::Object(fields)))
+    } else if options.print_schedule || spread {
+        let schedule = schedule()?;
+        let mut lines = vec![schedule.len().to_string()];
+        if let Some(bound) = lower_bound {
+            lines.push(format!("Lower bound: {}", bound));
+        }
+        if spread {
+            let max = max_by_page(instance, &schedule);
+            lines.push(format!("Spread: at most {} by page", max));
+        }
+        if options.print_schedule {
+            lines.extend(page_lines(instance, &schedule));
+        }
+        Ok(Output::Text(lines.join("\n")))
+    } else {
+        let n_pages = match &solution {
+            Some(solution) => solution.schedule.len(),
+            None => solver.min_pages()?,
+        };
+        let mut lines = vec![n_pages.to_string()];
+        if let Some(bound) = lower_bound {
+            lines.push(format!("Lower bound: {}", bound));
+        }
+        Ok(Output::Text(lines.join("\n")))
+    }
+}
 
-    #[test]
-    fn test_roots() {
-        let g = DependencyGraph::new(vec![(1, 4), (3, 2), (5, 3)], 6);
-        let expected: BTreeSet<_> = vec![1, 5, 6].into_iter().collect();
-        assert_eq!(g.roots(), expected);
-    }
-    #[test]
-    fn test_isolated_vertices() {
-        let g = DependencyGraph::new(vec![(1, 4), (3, 2), (5, 3), (5, 7)], 9);
-        let expected = vec![6, 8, 9];
-        assert_eq!(g.isolated_vertices(), expected);
-    }
-    #[test]
-    fn test_is_acyclic() {
-        let c4 = DependencyGraph::new(vec![(1, 4), (4, 2), (2, 3), (3, 1)], 4);
-        assert_eq!(c4.is_acyclic(), false);
-        let transitive_tournament = DependencyGraph::new(vec![(1, 3), (1, 2), (2, 3)], 3);
-        assert_eq!(transitive_tournament.is_acyclic(), true);
-    }
-    #[test]
-    fn example1() {
-        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
-        assert_eq!(min_pages(g1, 2), Some(3));
-    }
-    #[test]
-    fn example2() {
-        let g2 = DependencyGraph::new(vec![(2, 1), (3, 1), (4, 1), (1, 5)], 5);
-        assert_eq!(min_pages(g2, 2), Some(4));
-    }
-    #[test]
-    fn example3() {
-        let g3 = DependencyGraph::new(vec![], 11);
-        assert_eq!(min_pages(g3, 2), Some(6));
-    }
-    #[test]
-    fn impossible_example() {
-        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1)], 4);
-        assert_eq!(min_pages(g, 2), None);
-    }
-    #[test]
-    fn slow_example() {
-        let mut edges = Vec::new();
-        for u in 1..7 {
-            for v in 1..u {
-                edges.push((u, v))
+/// Format the content of each page, with its spread if the pages form spreads.
+fn page_lines(instance: &Instance, schedule: &Schedule) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (i, page) in schedule.iter().enumerate() {
+        let photos = page.iter().join(" ");
+        match &instance.spreads {
+            Some(spreads) => {
+                let spread = spreads.of_page(i) + 1;
+                lines.push(format!("Page {} (spread {}): {}", i + 1, spread, photos))
             }
+            None => lines.push(format!("Page {}: {}", i + 1, photos)),
+        }
+    }
+    lines
+}
+
+/// Return the number of slots taken on the fullest page.
+fn max_by_page(instance: &Instance, schedule: &Schedule) -> usize {
+    schedule
+        .iter()
+        .map(|page| instance.page_size(page))
+        .max()
+        .unwrap_or(0)
+}
+
+fn exit_with(error: SolveError) -> ! {
+    eprintln!("error: {}", error);
+    process::exit(EXIT_SOLVE_ERROR)
+}
+
+/// What a run prints: lines of text, or a JSON object with `--json`.
+enum Output {
+    Text(String),
+    Json(Json),
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Text(text) => f.write_str(text),
+            Output::Json(json) => write!(f, "{}", json),
         }
-        let g = DependencyGraph::new(edges, 15);
-        assert_eq!(min_pages(g, 3), Some(6));
-    }
-    #[test]
-    fn slower_example() {
-        // Star pointing to its root
-        let edges: Vec<_> = (1..12).map(|i| (i, 12)).collect();
-        let g = DependencyGraph::new(edges, 12);
-        assert_eq!(min_pages(g, 3), Some(5));
-    }
-    #[test]
-    fn path_example() {
-        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 4), (4, 5)], 8);
-        assert_eq!(min_pages(g, 4), Some(5));
-    }
-    #[test]
-    fn test_load_file() {
-        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
-        assert_eq!(
-            read_file("examples/example1").expect("Error reading file"),
-            (g1, 2)
-        )
     }
 }
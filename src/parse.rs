@@ -0,0 +1,766 @@
+use crate::capacity::Capacities;
+use crate::error::{Location, ParseError};
+use crate::graph::DependencyGraph;
+use crate::instance::{Instance, Pin, Spreads};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Number of photos an input may declare at most.
+const MAX_INPUT_PHOTOS: usize = 1 << 20;
+
+/// Read an instance from a file (see `parse` for the format).
+pub fn read_file<P: AsRef<Path>>(filename: P) -> Result<Instance, ParseError> {
+    Parser::new()
+        .read_file(filename)
+        .map(|parsed| parsed.instance)
+}
+
+/// Read an instance in strict mode (see `Parser::parse` for the format).
+pub fn parse<R: BufRead>(reader: R) -> Result<Instance, ParseError> {
+    Parser::new().parse(reader).map(|parsed| parsed.instance)
+}
+
+/// Options for reading instances.
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    lenient: bool,
+}
+
+/// An instance read by a `Parser`, with the problems tolerated in lenient mode.
+#[derive(Debug)]
+pub struct Parsed {
+    pub instance: Instance,
+    pub warnings: Vec<ParseError>,
+}
+
+impl Parser {
+    /// Return a parser in strict mode.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// In lenient mode, a number of edges different from the header
+    /// is reported as a warning instead of an error.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+    /// Read an instance from a file.
+    pub fn read_file<P: AsRef<Path>>(&self, filename: P) -> Result<Parsed, ParseError> {
+        let name = filename.as_ref().display().to_string();
+        File::open(filename)
+            .map_err(ParseError::from)
+            .and_then(|file| self.parse(BufReader::new(file)))
+            .map(|mut parsed| {
+                parsed.warnings = parsed
+                    .warnings
+                    .into_iter()
+                    .map(|warning| warning.in_file(&name))
+                    .collect();
+                parsed
+            })
+            .map_err(|error| error.in_file(&name))
+    }
+    /// Read an instance: a header line `n m k` (photos, photos by page, edges)
+    /// followed by `k` lines `u v`, one by edge. An edge with a lag is written `u v l`:
+    /// photo `v` is then at least `l` pages after photo `u`. A weak edge, with a lag of 0,
+    /// can also be written `u v =`.
+    ///
+    /// Lines starting with a keyword are directives:
+    /// - `capacities c_1 ... c_j`: the first `j` pages hold `c_1`, ..., `c_j` photos,
+    /// - `pattern p_1 ... p_q`: afterwards the pages hold `p_1`, ..., `p_q`, `p_1`, ...
+    ///   photos, instead of `m` photos each,
+    /// - `photo u s`: photo `u` takes `s` slots of its page instead of one (the size column
+    ///   is optional, and this line can be repeated, once by photo),
+    /// - `sizes s_1 ... s_n`: the sizes of all the photos on one line, which the `photo`
+    ///   lines override,
+    /// - `together u_1 ... u_j`: the photos `u_1`, ..., `u_j` share a page
+    ///   (this line can be repeated),
+    /// - `apart u v`: the photos `u` and `v` are on different pages (this line can be repeated),
+    /// - `page u p`, `release u p`, `deadline u p`: photo `u` is exactly on page `p`,
+    ///   on page `p` or after, on page `p` or before (pages are numbered from 1),
+    /// - `last u`: photo `u` is on the last page,
+    /// - `chapter u_1 ... u_j`: the photos `u_1`, ..., `u_j` form a chapter, no other chapter
+    ///   having photos from its first page to its last one (this line can be repeated,
+    ///   once by chapter),
+    /// - `spreads`: the pages face each other by two, page 1 being a single right-hand page,
+    ///   or `spreads paired` for page 1 to face page 2,
+    /// - `spread_together u_1 ... u_j`: the photos `u_1`, ..., `u_j` are on the same spread,
+    /// - `spread_apart u v`: the photos `u` and `v` are on different spreads (these two lines
+    ///   can be repeated, and imply `spreads`).
+    pub fn parse<R: BufRead>(&self, reader: R) -> Result<Parsed, ParseError> {
+        let mut lines = reader.lines();
+        let header = lines.next().transpose()?.ok_or(ParseError::MissingHeader {
+            location: Location::new(1, 1),
+        })?;
+        let header = tokens(&header, 1);
+        if header.len() != 3 {
+            let location = match header.get(3) {
+                Some(extra) => extra.location(),
+                None => Location::new(1, 1),
+            };
+            return Err(ParseError::HeaderArity {
+                location,
+                found: header.len(),
+            });
+        }
+        // The photos are allocated before any line is read.
+        let n_photos = header[0].number()?;
+        if n_photos > MAX_INPUT_PHOTOS {
+            return Err(ParseError::TooManyPhotos {
+                location: header[0].location(),
+                n_photos,
+                max: MAX_INPUT_PHOTOS,
+            });
+        }
+        let mut reader = Reader {
+            n_photos,
+            edges: Vec::new(),
+            first_extra_edge: None,
+            capacities: None,
+            pattern: None,
+            sizes: None,
+            photo_sizes: BTreeMap::new(),
+            together: Vec::new(),
+            apart: Vec::new(),
+            pins: BTreeMap::new(),
+            last: Vec::new(),
+            chapters: BTreeMap::new(),
+            n_chapters: 0,
+            first_alone: None,
+            spread_together: Vec::new(),
+            spread_apart: Vec::new(),
+        };
+        let m: usize = header[1].number()?;
+        let k: usize = header[2].number()?;
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let line_number = i + 2;
+            let end = Location::new(line_number, line.chars().count() + 1);
+            match &tokens(&line, line_number)[..] {
+                [] => (),
+                [keyword, arguments @ ..] if keyword.is_keyword() => {
+                    reader.directive(keyword, arguments, end)?
+                }
+                tokens => reader.edge(tokens, end, k)?,
+            }
+        }
+        let mut warnings = Vec::new();
+        let n_edges = reader.edges.len();
+        if n_edges != k {
+            let error = ParseError::EdgeCount {
+                location: reader
+                    .first_extra_edge
+                    .unwrap_or_else(|| header[2].location()),
+                declared: k,
+                found: n_edges,
+            };
+            if self.lenient {
+                warnings.push(error);
+            } else {
+                return Err(error);
+            }
+        }
+        let capacities = Capacities::new(
+            reader.capacities.unwrap_or_default(),
+            reader.pattern.unwrap_or_else(|| vec![m]),
+        );
+        let mut builder = DependencyGraph::builder(reader.n_photos).lagged_edges(reader.edges);
+        for group in &reader.together {
+            builder = builder.together(group);
+        }
+        for &(u, v) in &reader.apart {
+            builder = builder.apart(u, v);
+        }
+        for &photo in &reader.last {
+            builder = builder.last(photo);
+        }
+        let graph = builder
+            .build()
+            // The photos are checked when read
+            .unwrap();
+        let mut instance = Instance::with_capacities(graph, capacities);
+        if let Some(sizes) = reader.sizes {
+            instance.sizes = sizes;
+        }
+        for (photo, size) in reader.photo_sizes {
+            instance.sizes[photo as usize - 1] = size;
+        }
+        instance.pins = reader.pins;
+        instance.chapters = reader.chapters;
+        if reader.first_alone.is_some()
+            || !reader.spread_together.is_empty()
+            || !reader.spread_apart.is_empty()
+        {
+            let mut spreads = Spreads::new(reader.first_alone.unwrap_or(true));
+            spreads.together = reader.spread_together;
+            spreads.apart = reader.spread_apart;
+            instance.spreads = Some(spreads);
+        }
+        Ok(Parsed { instance, warnings })
+    }
+}
+
+/// What has been read so far from an input.
+struct Reader {
+    n_photos: usize,
+    /// Edges with their lag.
+    edges: Vec<(u32, u32, usize)>,
+    first_extra_edge: Option<Location>,
+    capacities: Option<Vec<usize>>,
+    pattern: Option<Vec<usize>>,
+    sizes: Option<Vec<usize>>,
+    /// Sizes given by the `photo` lines.
+    photo_sizes: BTreeMap<u32, usize>,
+    together: Vec<Vec<u32>>,
+    apart: Vec<(u32, u32)>,
+    pins: BTreeMap<u32, Pin>,
+    last: Vec<u32>,
+    chapters: BTreeMap<u32, usize>,
+    n_chapters: usize,
+    /// Whether page 1 is alone on its spread, if the spreads are given.
+    first_alone: Option<bool>,
+    spread_together: Vec<Vec<u32>>,
+    spread_apart: Vec<(u32, u32)>,
+}
+
+impl Reader {
+    /// Read an edge line `u v`, `u v l` for a lag of `l` pages or `u v =` for a weak edge,
+    /// the `k` of the header being `declared`.
+    fn edge(&mut self, tokens: &[Token], end: Location, declared: usize) -> Result<(), ParseError> {
+        match tokens {
+            [u, v, rest @ ..] => {
+                let (u, v) = (u.photo(self.n_photos)?, v.photo(self.n_photos)?);
+                let lag = match rest.first() {
+                    Some(token) if token.text == "=" => 0,
+                    Some(token) => token.number()?,
+                    None => 1,
+                };
+                if let Some(extra) = rest.get(1) {
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: "an edge has only two photos and an optional lag".to_string(),
+                    });
+                }
+                if u.1 == v.1 {
+                    return Err(ParseError::SelfLoop {
+                        location: v.0,
+                        photo: v.1,
+                    });
+                }
+                self.edges.push((u.1, v.1, lag));
+                if self.edges.len() == declared + 1 {
+                    self.first_extra_edge = Some(u.0);
+                }
+                Ok(())
+            }
+            _ => Err(ParseError::MissingPhoto { location: end }),
+        }
+    }
+    /// Read a directive line, `end` being the location of the end of the line.
+    fn directive(
+        &mut self,
+        keyword: &Token,
+        arguments: &[Token],
+        end: Location,
+    ) -> Result<(), ParseError> {
+        if keyword.text == "together" {
+            // Repeated once by group, like `apart`.
+            if arguments.is_empty() {
+                return Err(keyword.missing_argument(end));
+            }
+            let group = arguments
+                .iter()
+                .map(|token| token.photo(self.n_photos).map(|(_, photo)| photo))
+                .collect::<Result<_, _>>()?;
+            self.together.push(group);
+            return Ok(());
+        }
+        if keyword.text == "spread_together" {
+            if arguments.is_empty() {
+                return Err(keyword.missing_argument(end));
+            }
+            let group = arguments
+                .iter()
+                .map(|token| token.photo(self.n_photos).map(|(_, photo)| photo))
+                .collect::<Result<_, _>>()?;
+            self.spread_together.push(group);
+            return Ok(());
+        }
+        if keyword.text == "spreads" {
+            if self.first_alone.is_some() {
+                return Err(keyword.duplicate_directive());
+            }
+            let first_alone = match arguments {
+                [] => true,
+                [paired] if paired.text == "paired" => false,
+                [extra, ..] => {
+                    let extra = arguments.get(1).unwrap_or(extra);
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: "`spreads` takes only `paired`".to_string(),
+                    });
+                }
+            };
+            self.first_alone = Some(first_alone);
+            return Ok(());
+        }
+        if keyword.text == "chapter" {
+            // Each line starts a new chapter.
+            if arguments.is_empty() {
+                return Err(keyword.missing_argument(end));
+            }
+            let chapter = self.n_chapters;
+            for token in arguments {
+                let (location, photo) = token.photo(self.n_photos)?;
+                if self
+                    .chapters
+                    .insert(photo, chapter)
+                    .is_some_and(|c| c != chapter)
+                {
+                    return Err(ParseError::TwoChapters { location, photo });
+                }
+            }
+            self.n_chapters += 1;
+            return Ok(());
+        }
+        if let "page" | "release" | "deadline" = keyword.text {
+            let (photo, page) = match arguments {
+                [photo, page] => (photo.photo(self.n_photos)?.1, page.page()?),
+                [_, _, extra, ..] => {
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: format!("`{}` takes a photo and a page", keyword.text),
+                    })
+                }
+                _ => return Err(keyword.missing_argument(end)),
+            };
+            let pin = match keyword.text {
+                "page" => Pin::exactly(page),
+                "release" => Pin::release(page),
+                _ => Pin::deadline(page),
+            };
+            let previous = self.pins.get(&photo).copied().unwrap_or_default();
+            self.pins.insert(photo, previous.and(pin));
+            return Ok(());
+        }
+        if keyword.text == "last" {
+            match arguments {
+                [photo] => self.last.push(photo.photo(self.n_photos)?.1),
+                [_, extra, ..] => {
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: "`last` takes a photo".to_string(),
+                    })
+                }
+                [] => return Err(keyword.missing_argument(end)),
+            }
+            return Ok(());
+        }
+        if let "apart" | "spread_apart" = keyword.text {
+            let (u, v) = match arguments {
+                [u, v] => (u.photo(self.n_photos)?, v.photo(self.n_photos)?),
+                [_, _, extra, ..] => {
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: format!("`{}` takes two photos", keyword.text),
+                    })
+                }
+                _ => return Err(keyword.missing_argument(end)),
+            };
+            if u.1 == v.1 {
+                return Err(ParseError::SelfApart {
+                    location: v.0,
+                    photo: v.1,
+                });
+            }
+            if keyword.text == "apart" {
+                self.apart.push((u.1, v.1));
+            } else {
+                self.spread_apart.push((u.1, v.1));
+            }
+            return Ok(());
+        }
+        if keyword.text == "photo" {
+            let (photo, size) = match arguments {
+                [photo] => (photo.photo(self.n_photos)?, 1),
+                [photo, size] => (photo.photo(self.n_photos)?, size.number()?),
+                [_, _, extra, ..] => {
+                    return Err(ParseError::ExtraNumber {
+                        location: extra.location(),
+                        token: extra.text.to_string(),
+                        expected: "`photo` takes a photo and its size".to_string(),
+                    })
+                }
+                [] => return Err(keyword.missing_argument(end)),
+            };
+            if size == 0 {
+                return Err(ParseError::ZeroSize {
+                    location: arguments[1].location(),
+                    photo: photo.1,
+                });
+            }
+            if self.photo_sizes.insert(photo.1, size).is_some() {
+                return Err(ParseError::DuplicateDirective {
+                    location: keyword.location(),
+                    directive: format!("photo {}", photo.1),
+                });
+            }
+            return Ok(());
+        }
+        let field = match keyword.text {
+            "capacities" => &mut self.capacities,
+            "pattern" => &mut self.pattern,
+            "sizes" => &mut self.sizes,
+            _ => return Err(keyword.unknown_directive()),
+        };
+        if field.is_some() {
+            return Err(keyword.duplicate_directive());
+        }
+        if arguments.is_empty() {
+            return Err(keyword.missing_argument(end));
+        }
+        let values = numbers(arguments)?;
+        if keyword.text == "sizes" {
+            // One size by photo, each taking at least one slot.
+            if let Some(extra) = arguments.get(self.n_photos) {
+                return Err(ParseError::ExtraNumber {
+                    location: extra.location(),
+                    token: extra.text.to_string(),
+                    expected: format!("`sizes` has only {} values", self.n_photos),
+                });
+            }
+            if values.len() < self.n_photos {
+                return Err(keyword.missing_argument(end));
+            }
+            if let Some(i) = values.iter().position(|&size| size == 0) {
+                return Err(ParseError::ZeroSize {
+                    location: arguments[i].location(),
+                    photo: i as u32 + 1,
+                });
+            }
+        }
+        *field = Some(values);
+        Ok(())
+    }
+}
+
+/// A whitespace separated word of the input, with its position.
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl Token<'_> {
+    fn location(&self) -> Location {
+        Location::new(self.line, self.column)
+    }
+    /// Directives start with a keyword instead of a number.
+    fn is_keyword(&self) -> bool {
+        self.text.starts_with(|c: char| c.is_alphabetic())
+    }
+    fn unknown_directive(&self) -> ParseError {
+        ParseError::UnknownDirective {
+            location: self.location(),
+            token: self.text.to_string(),
+        }
+    }
+    fn duplicate_directive(&self) -> ParseError {
+        ParseError::DuplicateDirective {
+            location: self.location(),
+            directive: self.text.to_string(),
+        }
+    }
+    /// Report that this directive is incomplete at `end`.
+    fn missing_argument(&self, end: Location) -> ParseError {
+        ParseError::MissingArgument {
+            location: end,
+            directive: self.text.to_string(),
+        }
+    }
+    fn number<T: FromStr>(&self) -> Result<T, ParseError> {
+        self.text.parse().map_err(|_| ParseError::InvalidNumber {
+            location: self.location(),
+            token: self.text.to_string(),
+        })
+    }
+    /// Parse a page number, from 1.
+    fn page(&self) -> Result<usize, ParseError> {
+        match self.number()? {
+            0 => Err(ParseError::PageZero {
+                location: self.location(),
+            }),
+            page => Ok(page),
+        }
+    }
+    /// Parse a photo in `1..=n_photos`, returned with its location.
+    fn photo(&self, n_photos: usize) -> Result<(Location, u32), ParseError> {
+        let photo: u32 = self.number()?;
+        if photo == 0 || photo as usize > n_photos {
+            return Err(ParseError::PhotoOutOfRange {
+                location: self.location(),
+                photo,
+                n_photos,
+            });
+        }
+        Ok((self.location(), photo))
+    }
+}
+
+/// Parse a sequence of numbers.
+fn numbers<T: FromStr>(tokens: &[Token]) -> Result<Vec<T>, ParseError> {
+    tokens.iter().map(Token::number).collect()
+}
+
+/// Split a line into tokens.
+fn tokens(line: &str, line_number: usize) -> Vec<Token<'_>> {
+    let mut result = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in line.char_indices().chain([(line.len(), ' ')]).enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column, i)),
+            (Some((start_column, start_i)), true) => {
+                result.push(Token {
+                    text: &line[start_i..i],
+                    line: line_number,
+                    column: start_column + 1,
+                });
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the message and the position of the error reading `input`.
+    fn error(input: &str) -> (String, usize, usize) {
+        let error = parse(input.as_bytes()).unwrap_err();
+        let location = error.location().unwrap().clone();
+        (error.to_string(), location.line, location.column)
+    }
+
+    #[test]
+    fn test_load_file() {
+        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
+        assert_eq!(
+            read_file("examples/example1").expect("Error reading file"),
+            Instance::new(g1, 2)
+        )
+    }
+    #[test]
+    fn test_tokens() {
+        let tokens = tokens("  12 \t3  x", 7);
+        let found: Vec<_> = tokens.iter().map(|t| (t.text, t.line, t.column)).collect();
+        assert_eq!(found, vec![("12", 7, 3), ("3", 7, 7), ("x", 7, 10)]);
+    }
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            parse("".as_bytes()),
+            Err(ParseError::MissingHeader { .. })
+        ));
+        assert_eq!(error("3 2\n").1, 1);
+        assert_eq!(error("3 2 1 4\n").2, 7);
+        assert_eq!(
+            error("3 2 1\n1 x\n"),
+            ("2:3: `x` is not a valid number".to_string(), 2, 3)
+        );
+        let (message, line, column) = error("3 2 1\n\n1 2 3 1\n");
+        assert!(message.contains("only two photos"));
+        assert_eq!((line, column), (3, 7));
+        assert_eq!(error("3 2 1\n 1\n").2, 3);
+        assert_eq!(error("3 2 1\n1 2 = =\n").2, 7);
+        assert_eq!(error("3 2 1\n1 4\n").0, "2:3: photo 4 is not in 1..=3");
+        assert_eq!(error("3 2 1\n0 1\n").2, 1);
+        assert_eq!(
+            error("3 2 1\n2 2\n").0,
+            "2:3: photo 2 cannot come before itself"
+        );
+    }
+    #[test]
+    fn test_edge_count() {
+        assert_eq!(
+            error("3 2 2\n1 2\n"),
+            (
+                "1:5: the header declares 2 edges but 1 were found".to_string(),
+                1,
+                5
+            )
+        );
+        assert_eq!(error("3 2 1\n1 2\n\n2 3\n1 3\n").1, 4);
+        let parsed = Parser::new()
+            .lenient(true)
+            .parse("3 2 1\n1 2\n2 3\n".as_bytes());
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.instance.graph.edges().count(), 2);
+        assert!(matches!(
+            parsed.warnings[..],
+            [ParseError::EdgeCount {
+                declared: 1,
+                found: 2,
+                ..
+            }]
+        ));
+        let parsed = Parser::new().parse("3 2 1\n1 2\n".as_bytes()).unwrap();
+        assert!(parsed.warnings.is_empty());
+    }
+    #[test]
+    fn test_capacities() {
+        let instance = parse("4 3 1\ncapacities 1 2\n1 2\n".as_bytes()).unwrap();
+        assert_eq!(instance.capacities, Capacities::new(vec![1, 2], vec![3]));
+        let instance = parse("4 3 0\npattern 2 4\ncapacities 1\n".as_bytes()).unwrap();
+        assert_eq!(instance.capacities, Capacities::new(vec![1], vec![2, 4]));
+        assert_eq!(
+            error("4 3 0\ncapacity 1\n"),
+            ("2:1: unknown directive `capacity`".to_string(), 2, 1)
+        );
+        assert_eq!(error("4 3 0\npattern\n").2, 8);
+        assert_eq!(error("4 3 0\npattern 1 x\n").2, 11);
+        assert_eq!(error("4 3 0\npattern 1\npattern 2\n").1, 3);
+    }
+    #[test]
+    fn test_weak_edges() {
+        let instance = parse("3 2 3\n1 2 =\n2 1 =\n2 3\n".as_bytes()).unwrap();
+        assert_eq!(instance.graph.edges().collect::<Vec<_>>(), vec![(2, 3)]);
+        let weak: Vec<_> = instance.graph.weak_edges().collect();
+        assert_eq!(weak, vec![(1, 2), (2, 1)]);
+        assert_eq!(error("3 2 1\n1 1 =\n").2, 3);
+    }
+    #[test]
+    fn test_lags() {
+        let instance = parse("3 2 3\n1 2 0\n2 3 1\n1 3 3\n".as_bytes()).unwrap();
+        let graph = &instance.graph;
+        assert_eq!(graph.weak_edges().collect::<Vec<_>>(), vec![(1, 2)]);
+        assert_eq!(graph.edges().collect::<Vec<_>>(), vec![(1, 3), (2, 3)]);
+        assert_eq!(graph.lagged_edges().collect::<Vec<_>>(), vec![(1, 3, 3)]);
+        assert_eq!(error("3 2 1\n1 2 x\n").2, 5);
+        assert_eq!(error("3 2 1\n1 2 2 4\n").2, 7);
+    }
+    #[test]
+    fn test_together() {
+        let input = "4 2 0\ntogether 1 3\ntogether 4 2\n";
+        let weak: Vec<_> = parse(input.as_bytes())
+            .unwrap()
+            .graph
+            .weak_edges()
+            .collect();
+        assert_eq!(weak, vec![(1, 3), (2, 4), (3, 1), (4, 2)]);
+        assert_eq!(error("4 2 0\ntogether 1 5\n").2, 12);
+        assert_eq!(error("4 2 0\ntogether\n").2, 9);
+    }
+    #[test]
+    fn test_apart() {
+        let input = "4 2 0\napart 3 1\napart 2 4\n";
+        let apart: Vec<_> = parse(input.as_bytes())
+            .unwrap()
+            .graph
+            .apart_pairs()
+            .collect();
+        assert_eq!(apart, vec![(1, 3), (2, 4)]);
+        assert_eq!(error("4 2 0\napart 1\n").2, 8);
+        assert_eq!(
+            error("4 2 0\napart 1 2 3\n").0,
+            "2:11: unexpected `3`, `apart` takes two photos"
+        );
+        assert_eq!(
+            error("4 2 0\napart 2 2\n").0,
+            "2:9: photo 2 cannot be apart from itself"
+        );
+    }
+    #[test]
+    fn test_chapters() {
+        let input = "4 2 0\nchapter 3 1\nchapter 2\n";
+        let chapters = parse(input.as_bytes()).unwrap().chapters;
+        assert_eq!(chapters, vec![(1, 0), (2, 1), (3, 0)].into_iter().collect());
+        assert_eq!(error("4 2 0\nchapter\n").2, 8);
+        assert_eq!(
+            error("4 2 0\nchapter 1 2\nchapter 3 1\n").0,
+            "3:11: photo 1 is already in another chapter"
+        );
+    }
+    #[test]
+    fn test_spreads() {
+        let input = "4 2 0\nspread_together 1 3\nspread_apart 2 4\n";
+        let mut spreads = Spreads::new(true);
+        spreads.together = vec![vec![1, 3]];
+        spreads.apart = vec![(2, 4)];
+        assert_eq!(parse(input.as_bytes()).unwrap().spreads, Some(spreads));
+        let spreads = parse("4 2 0\nspreads paired\n".as_bytes()).unwrap().spreads;
+        assert_eq!(spreads, Some(Spreads::new(false)));
+        assert_eq!(parse("4 2 0\n".as_bytes()).unwrap().spreads, None);
+        assert_eq!(
+            error("4 2 0\nspreads open\n").0,
+            "2:9: unexpected `open`, `spreads` takes only `paired`"
+        );
+        assert_eq!(
+            error("4 2 0\nspreads\nspreads\n").0,
+            "3:1: `spreads` is given twice"
+        );
+        assert_eq!(
+            error("4 2 0\nspread_apart 3 3\n").0,
+            "2:16: photo 3 cannot be apart from itself"
+        );
+    }
+    #[test]
+    fn test_pins() {
+        let input = "4 2 0\npage 1 1\nrelease 2 3\ndeadline 2 4\nlast 4\n";
+        let instance = parse(input.as_bytes()).unwrap();
+        assert_eq!(instance.pin(1), Pin::exactly(1));
+        assert_eq!(instance.pin(2), Pin::release(3).and(Pin::deadline(4)));
+        assert_eq!(instance.pin(3), Pin::default());
+        assert_eq!(instance.graph.weak_edges().count(), 0);
+        assert_eq!(instance.graph.last_photos().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(error("4 2 0\npage 1\n").2, 7);
+        assert_eq!(
+            error("4 2 0\nrelease 1 0\n").0,
+            "2:11: pages are numbered from 1"
+        );
+        assert_eq!(error("4 2 0\nlast 1 2\n").2, 8);
+    }
+    #[test]
+    fn test_sizes() {
+        let instance = parse("3 3 1\n1 2\nsizes 2 1 3\n".as_bytes()).unwrap();
+        assert_eq!(instance.sizes, vec![2, 1, 3]);
+        assert_eq!(parse("3 3 0\n".as_bytes()).unwrap().sizes, vec![1, 1, 1]);
+        assert_eq!(error("3 3 0\nsizes 1 1\n").2, 10);
+        assert_eq!(
+            error("99999999999 3 0\n").0,
+            "1:1: 99999999999 photos declared, at most 1048576 are supported"
+        );
+        // The size column of the photo lines, 1 when left out, overrides `sizes`.
+        let instance = parse("3 3 0\nsizes 2 2 2\nphoto 1 3\nphoto 3\n".as_bytes()).unwrap();
+        assert_eq!(instance.sizes, vec![3, 2, 1]);
+        assert_eq!(
+            error("3 3 0\nphoto 2 2\nphoto 2 1\n").0,
+            "3:1: `photo 2` is given twice"
+        );
+        assert_eq!(error("3 3 0\nphoto 2 0\n").2, 9);
+        assert_eq!(error("3 3 0\nphoto 4 1\n").2, 7);
+        assert_eq!(
+            error("3 3 0\nsizes 1 1 1 2\n").0,
+            "2:13: unexpected `2`, `sizes` has only 3 values"
+        );
+        assert_eq!(
+            error("3 3 0\nsizes 1 0 1\n").0,
+            "2:9: photo 2 cannot have size 0"
+        );
+    }
+    #[test]
+    fn test_file_in_error() {
+        let error = read_file("examples/does-not-exist").unwrap_err();
+        assert!(error.to_string().starts_with("examples/does-not-exist: "));
+    }
+}
@@ -0,0 +1,34 @@
+//! Seeded pseudo-random numbers, so that random drafts can be drawn again from their seed.
+//!
+//! This is the SplitMix64 generator: small, fast and good enough to pick among schedules,
+//! without depending on a crate whose output could change between versions.
+
+/// A pseudo-random number generator given by its seed.
+#[derive(Clone, Debug)]
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// Return a number in `0..bound` (uniformly), `bound` being positive.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // Reject the last incomplete range of `bound` numbers to avoid any bias.
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let n = self.next_u64();
+            if n < zone {
+                return n % bound;
+            }
+        }
+    }
+}
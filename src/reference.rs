@@ -0,0 +1,329 @@
+//! Reference implementation: the plain Case 1/2/3 recursion on `DependencyGraph`.
+//!
+//! It is exponential in the worst case but simple enough to be trusted,
+//! so the other solvers are cross-checked against it in tests. The solver runs
+//! the same recursion as a branch and bound, in `search`.
+
+use crate::capacity::{all_pages, maximal_pages};
+use crate::graph::DependencyGraph;
+use crate::instance::{Instance, Schedule, Waits};
+use std::cmp::max;
+
+/// Compute the minimum number of pages of an instance,
+/// or `None` if it has no schedule.
+pub(crate) fn min_pages(instance: &Instance) -> Option<usize> {
+    min_pages_schedule(instance).map(|schedule| schedule.len())
+}
+
+/// Compute an optimal assignment of the photos to pages, one entry per page.
+pub(crate) fn min_pages_schedule(instance: &Instance) -> Option<Schedule> {
+    assert!(instance.capacities.min() > 0);
+    if !instance.graph.is_acyclic() {
+        return None;
+    }
+    schedule_feasible(instance.graph.clone(), instance, 0, Waits::new(), None, &[])
+}
+
+/// Return an optimal schedule for the photos of `graph` from page `page` on,
+/// the photos of `waits` being held back by lags, the chapter `current` being
+/// still open and the photos of `spread` being on the previous pages of the spread,
+/// assuming the graph is acyclic, or `None` if the pins, chapters or spreads cannot be met.
+fn schedule_feasible(
+    mut graph: DependencyGraph,
+    instance: &Instance,
+    page: usize,
+    waits: Waits,
+    current: Option<usize>,
+    spread: &[u32],
+) -> Option<Schedule> {
+    let n_photos = graph.count_vertices();
+    if n_photos == 0 {
+        return Some(Vec::new());
+    };
+    if graph
+        .adj_list
+        .keys()
+        .any(|&photo| !instance.before_deadline(photo, page))
+    {
+        return None;
+    }
+    let capacities = &instance.capacities;
+    let max_by_page = capacities.of_page(page);
+    if instance.one_by_page() {
+        // Photos linked by a cycle of weak edges cannot share a page of one photo.
+        let schedule = graph
+            .topological_order()?
+            .into_iter()
+            .map(|photo| vec![photo])
+            .collect();
+        return Some(schedule);
+    }
+    let waiting = |photo: u32| waits.binary_search_by_key(&photo, |&(v, _)| v).is_ok();
+    let total_size: usize = graph.adj_list.keys().map(|&v| instance.size(v)).sum();
+    let spread_photos: Vec<_> = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| spreads.photos())
+        .collect();
+    // Get the photos that can go anywhere and fill any free spot
+    let photos_no_dependency: Vec<_> = graph
+        .isolated_vertices()
+        .into_iter()
+        .filter(|&photo| {
+            instance.size(photo) == 1
+                && !instance.pins.contains_key(&photo)
+                && instance.chapter(photo).is_none()
+                && !waiting(photo)
+                && !spread_photos.contains(&photo)
+        })
+        .collect();
+    // Case 1: Photos without dependency can be added anywhere afterwards
+    // as long as there are enough pages to hold all the photos.
+    if !photos_no_dependency.is_empty() {
+        for &photo in &photos_no_dependency {
+            graph.remove(photo);
+        }
+        let mut schedule = schedule_feasible(graph, instance, page, waits, current, spread)?;
+        schedule.resize(
+            max(capacities.pages_for(page, total_size), schedule.len()),
+            Vec::new(),
+        );
+        // Fill the free spots page by page
+        let mut free_photos = photos_no_dependency.into_iter();
+        for (capacity, photos) in capacities.from_page(page).zip(&mut schedule) {
+            let free_spots = capacity - instance.page_size(photos);
+            photos.extend(free_photos.by_ref().take(free_spots));
+        }
+        return Some(schedule);
+    }
+    // The chapters with photos placed, and those with photos left once `placed` are too.
+    let started = |chapter| {
+        instance
+            .chapters
+            .iter()
+            .any(|(photo, &c)| c == chapter && !graph.adj_list.contains_key(photo))
+    };
+    let has_left = |chapter, placed: &[u32]| {
+        instance.chapters.iter().any(|(photo, &c)| {
+            c == chapter && graph.adj_list.contains_key(photo) && !placed.contains(photo)
+        })
+    };
+    // The photos apart from one on the previous pages of the spread wait for the next one.
+    let spread_apart: Vec<u32> = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| &spreads.apart)
+        .filter_map(|&(u, v)| {
+            if spread.contains(&u) {
+                Some(v)
+            } else if spread.contains(&v) {
+                Some(u)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let waiting_pages = !waits.is_empty() || page < instance.pinned_pages();
+    let closes = instance.closes_spread(page);
+    let mut result: Option<Schedule> = None;
+    for chapter in instance.next_chapters(current, started) {
+        // Get the photos that can go on the next page
+        let photos_ready: Vec<_> = graph
+            .ready_where(|photo| {
+                instance.pin(photo).allows(page)
+                    && !waiting(photo)
+                    && !spread_apart.contains(&photo)
+                    && instance.chapter(photo).is_none_or(|c| Some(c) == chapter)
+            })
+            .into_iter()
+            .collect();
+        if chapter.is_some()
+            && chapter != current
+            && photos_ready
+                .iter()
+                .all(|&photo| instance.chapter(photo).is_none())
+        {
+            // The chapter cannot start yet.
+            continue;
+        }
+        let pages = if let Some(spreads) = instance
+            .spreads
+            .as_ref()
+            .filter(|spreads| spreads.constrain())
+        {
+            // Try every page, but a blank spread only when waiting for a lag or a pin
+            // (a blank first page alone moves the next pages to the other side),
+            // and a group on the spread must be complete when it ends.
+            let empty_allowed = waiting_pages || !closes || !spread.is_empty() || page == 0;
+            let complete = |photos: &[u32]| {
+                spreads.together.iter().all(|group| {
+                    let left =
+                        |photo: &u32| graph.adj_list.contains_key(photo) && !photos.contains(photo);
+                    group.iter().all(left) || group.iter().all(|photo| !left(photo))
+                })
+            };
+            all_pages(&instance.page_units(&photos_ready), max_by_page)
+                .into_iter()
+                .filter(|photos| {
+                    (!photos.is_empty() || empty_allowed) && (!closes || complete(photos))
+                })
+                .collect()
+        } else if photos_ready.is_empty() && !waiting_pages {
+            // An empty page would not change anything.
+            continue;
+        // Case 2: All ready-to-use photos fit in the next page (and none are apart).
+        } else if instance.page_size(&photos_ready) <= max_by_page
+            && !instance.graph.has_apart_pair(&photos_ready)
+        {
+            vec![photos_ready]
+        // Case 3: Try all the ways to fill the next page.
+        } else {
+            maximal_pages(&instance.page_units(&photos_ready), max_by_page)
+        };
+        for photos in pages {
+            // The chapter stays open if it has started and has photos left.
+            let next_chapter = chapter.filter(|&c| {
+                (current == Some(c)
+                    || photos
+                        .iter()
+                        .any(|&photo| instance.chapter(photo) == Some(c)))
+                    && has_left(c, &photos)
+            });
+            let mut subgraph = graph.clone();
+            for &photo in &photos {
+                subgraph.remove(photo);
+            }
+            let waits = instance.next_waits(&waits, &photos);
+            let next_spread = if instance.spread_constrained() && !closes {
+                [spread, &photos].concat()
+            } else {
+                Vec::new()
+            };
+            let Some(rest) = schedule_feasible(
+                subgraph,
+                instance,
+                page + 1,
+                waits,
+                next_chapter,
+                &next_spread,
+            ) else {
+                continue;
+            };
+            if result
+                .as_ref()
+                .is_none_or(|best| 1 + rest.len() < best.len())
+            {
+                let mut schedule = vec![photos];
+                schedule.extend(rest);
+                result = Some(schedule);
+            }
+        }
+    }
+    result
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::Instance;
+    use crate::solver::{Method, Solver, Statistics};
+    use crate::special::Class;
+
+    /// Check a schedule with the constraints of the corresponding instance.
+    fn check_schedule(graph: &DependencyGraph, max_by_page: usize, schedule: &[Vec<u32>]) {
+        let instance = Instance::new(graph.clone(), max_by_page);
+        assert!(instance.is_valid_schedule(schedule));
+    }
+    #[test]
+    fn example1() {
+        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
+        assert_eq!(min_pages(&Instance::new(g1, 2)), Some(3));
+    }
+    #[test]
+    fn example2() {
+        let g2 = DependencyGraph::new(vec![(2, 1), (3, 1), (4, 1), (1, 5)], 5);
+        assert_eq!(min_pages(&Instance::new(g2, 2)), Some(4));
+    }
+    #[test]
+    fn example3() {
+        let g3 = DependencyGraph::new(vec![], 11);
+        assert_eq!(min_pages(&Instance::new(g3, 2)), Some(6));
+    }
+    #[test]
+    fn impossible_example() {
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1)], 4);
+        assert_eq!(min_pages(&Instance::new(g, 2)), None);
+    }
+    #[test]
+    fn slow_example() {
+        let mut edges = Vec::new();
+        for u in 1..7 {
+            for v in 1..u {
+                edges.push((u, v))
+            }
+        }
+        let g = DependencyGraph::new(edges, 15);
+        assert_eq!(min_pages(&Instance::new(g, 3)), Some(6));
+    }
+    #[test]
+    fn slower_example() {
+        // Star pointing to its root
+        let edges: Vec<_> = (1..12).map(|i| (i, 12)).collect();
+        let instance = Instance::new(DependencyGraph::new(edges, 12), 3);
+        assert_eq!(min_pages(&instance), Some(5));
+        // The solver leaves it to Hu's algorithm, without any search.
+        assert_eq!(Class::of(&instance), Some(Class::InForest));
+        let solver = Solver::new(&instance).method(Method::Reference);
+        assert_eq!(solver.min_pages(), Ok(5));
+        assert_eq!(solver.statistics(), Statistics::default());
+    }
+    #[test]
+    fn path_example() {
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 4), (4, 5)], 8);
+        assert_eq!(min_pages(&Instance::new(g, 4)), Some(5));
+    }
+    #[test]
+    fn test_schedule() {
+        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
+        let schedule = min_pages_schedule(&Instance::new(g1.clone(), 2)).unwrap();
+        assert_eq!(schedule, vec![vec![2, 3], vec![1], vec![4]]);
+        check_schedule(&g1, 2, &schedule);
+    }
+    #[test]
+    fn test_schedule_isolated_photos() {
+        let edges = vec![(1, 2), (2, 3), (3, 4), (4, 5)];
+        let g = DependencyGraph::new(edges, 8);
+        let schedule = min_pages_schedule(&Instance::new(g.clone(), 4)).unwrap();
+        assert_eq!(schedule.len(), 5);
+        check_schedule(&g, 4, &schedule);
+        let g3 = DependencyGraph::new(vec![], 11);
+        let schedule = min_pages_schedule(&Instance::new(g3.clone(), 2)).unwrap();
+        assert_eq!(schedule.len(), 6);
+        check_schedule(&g3, 2, &schedule);
+    }
+    #[test]
+    fn test_schedule_case3() {
+        let edges: Vec<_> = (1..12).map(|i| (i, 12)).collect();
+        let g = DependencyGraph::new(edges, 12);
+        let schedule = min_pages_schedule(&Instance::new(g.clone(), 3)).unwrap();
+        assert_eq!(schedule.len(), 5);
+        check_schedule(&g, 3, &schedule);
+        let g1 = DependencyGraph::new(vec![(2, 1), (3, 1), (1, 4)], 4);
+        let schedule = min_pages_schedule(&Instance::new(g1.clone(), 1)).unwrap();
+        assert_eq!(schedule.len(), 4);
+        check_schedule(&g1, 1, &schedule);
+    }
+    #[test]
+    fn test_schedule_impossible() {
+        let g = DependencyGraph::new(vec![(1, 2), (2, 3), (3, 1)], 4);
+        assert_eq!(min_pages_schedule(&Instance::new(g, 2)), None);
+        // A group sharing a page cannot go on pages of one photo.
+        let g = DependencyGraph::builder(3)
+            .together(&[1, 2])
+            .edge(2, 3)
+            .build()
+            .unwrap();
+        assert_eq!(min_pages_schedule(&Instance::new(g, 1)), None);
+    }
+}
@@ -0,0 +1,399 @@
+//! Branch and bound on the Case 1/2/3 recursion of the reference implementation,
+//! run by the solver for the reference method and the albums too large for the bitmask.
+//!
+//! A greedy descent first gives a schedule, and the branches whose lower bound
+//! cannot beat the best schedule found are cut.
+//! When the search is stopped by its limits, the best schedule found so far is returned.
+//! Within limits, each node also tries a bounded number of pages, so that albums
+//! with many photos ready at once still get past their first pages.
+
+use crate::bound::{lower_bound, tails};
+use crate::capacity::first_pages;
+use crate::graph::DependencyGraph;
+use crate::instance::{Instance, Schedule, Waits};
+use crate::solver::Statistics;
+use crate::stop::Stop;
+use std::cmp::{max, Reverse};
+
+/// Compute an optimal assignment of the photos to pages, or one shorter than `incumbent`
+/// if any (returning `incumbent` otherwise), adding the nodes of the search to `statistics`.
+///
+/// The greedy descent is not counted against the node budget, but stops with the time limit
+/// or the cancellation. Once `stop` is reached, or if some pages were left out, the schedule
+/// is the best found but not proven optimal.
+pub(crate) fn search(
+    instance: &Instance,
+    statistics: &mut Statistics,
+    stop: &Stop,
+    incumbent: Option<Schedule>,
+) -> Option<Schedule> {
+    assert!(instance.capacities.min() > 0);
+    if !instance.graph.is_acyclic() {
+        return None;
+    }
+    let mut search = Search {
+        instance,
+        greedy: true,
+        statistics: Statistics::default(),
+        stop,
+    };
+    let start = |search: &mut Search, bound| {
+        schedule_feasible(
+            instance.graph.clone(),
+            search,
+            0,
+            Waits::new(),
+            None,
+            &[],
+            bound,
+        )
+    };
+    // The shorter of the greedy schedule and the incumbent, if any, is the one to beat.
+    let greedy = start(&mut search, usize::MAX);
+    let best = greedy.into_iter().chain(incumbent).min_by_key(Vec::len);
+    search.greedy = false;
+    search.statistics = Statistics::default();
+    let bound = best.as_ref().map_or(usize::MAX, Vec::len);
+    let schedule = start(&mut search, bound).or(best);
+    *statistics += search.statistics;
+    schedule
+}
+
+/// State shared by the whole recursion.
+struct Search<'a> {
+    instance: &'a Instance,
+    /// Only follow the most promising page at each step, without backtracking.
+    greedy: bool,
+    statistics: Statistics,
+    stop: &'a Stop,
+}
+
+/// Return an optimal schedule for the photos of `graph` from page `page` on,
+/// the photos of `waits` being held back by lags, the chapter `current` being
+/// still open and the photos of `spread` being on the previous pages of the spread,
+/// assuming the graph is acyclic, or `None` if the pins, chapters or spreads cannot be met
+/// or if no schedule has fewer than `bound` pages (or none was found before the search stopped).
+fn schedule_feasible(
+    mut graph: DependencyGraph,
+    search: &mut Search,
+    page: usize,
+    waits: Waits,
+    current: Option<usize>,
+    spread: &[u32],
+    bound: usize,
+) -> Option<Schedule> {
+    let instance = search.instance;
+    let stopped = if search.greedy {
+        search.stop.interrupted()
+    } else {
+        search.stop.node()
+    };
+    if stopped {
+        return None;
+    }
+    search.statistics.nodes += 1;
+    let tails = tails(&graph);
+    if lower_bound(&graph, instance, page, &waits, &tails) >= bound {
+        search.statistics.pruned += 1;
+        return None;
+    }
+    let n_photos = graph.count_vertices();
+    if n_photos == 0 {
+        return Some(Vec::new());
+    };
+    if graph
+        .adj_list
+        .keys()
+        .any(|&photo| !instance.before_deadline(photo, page))
+    {
+        return None;
+    }
+    let capacities = &instance.capacities;
+    let max_by_page = capacities.of_page(page);
+    if instance.one_by_page() {
+        // Photos linked by a cycle of weak edges cannot share a page of one photo.
+        let schedule = graph
+            .topological_order()?
+            .into_iter()
+            .map(|photo| vec![photo])
+            .collect();
+        return Some(schedule);
+    }
+    let waiting = |photo: u32| waits.binary_search_by_key(&photo, |&(v, _)| v).is_ok();
+    let total_size: usize = graph.adj_list.keys().map(|&v| instance.size(v)).sum();
+    let spread_photos: Vec<_> = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| spreads.photos())
+        .collect();
+    // Get the photos that can go anywhere and fill any free spot
+    let photos_no_dependency: Vec<_> = graph
+        .isolated_vertices()
+        .into_iter()
+        .filter(|&photo| {
+            instance.size(photo) == 1
+                && !instance.pins.contains_key(&photo)
+                && instance.chapter(photo).is_none()
+                && !waiting(photo)
+                && !spread_photos.contains(&photo)
+        })
+        .collect();
+    // Case 1: Photos without dependency can be added anywhere afterwards
+    // as long as there are enough pages to hold all the photos.
+    if !photos_no_dependency.is_empty() {
+        for &photo in &photos_no_dependency {
+            graph.remove(photo);
+        }
+        let mut schedule = schedule_feasible(graph, search, page, waits, current, spread, bound)?;
+        schedule.resize(
+            max(capacities.pages_for(page, total_size), schedule.len()),
+            Vec::new(),
+        );
+        // Fill the free spots page by page
+        let mut free_photos = photos_no_dependency.into_iter();
+        for (capacity, photos) in capacities.from_page(page).zip(&mut schedule) {
+            let free_spots = capacity - instance.page_size(photos);
+            photos.extend(free_photos.by_ref().take(free_spots));
+        }
+        return Some(schedule);
+    }
+    // The chapters with photos placed, and those with photos left once `placed` are too.
+    let started = |chapter| {
+        instance
+            .chapters
+            .iter()
+            .any(|(photo, &c)| c == chapter && !graph.adj_list.contains_key(photo))
+    };
+    let has_left = |chapter, placed: &[u32]| {
+        instance.chapters.iter().any(|(photo, &c)| {
+            c == chapter && graph.adj_list.contains_key(photo) && !placed.contains(photo)
+        })
+    };
+    // The photos apart from one on the previous pages of the spread wait for the next one.
+    let spread_apart: Vec<u32> = instance
+        .spreads
+        .iter()
+        .flat_map(|spreads| &spreads.apart)
+        .filter_map(|&(u, v)| {
+            if spread.contains(&u) {
+                Some(v)
+            } else if spread.contains(&v) {
+                Some(u)
+            } else {
+                None
+            }
+        })
+        .collect();
+    // The pages to try, the first ones only within limits.
+    let stop = search.stop;
+    let pages_of = |photos: &[u32], only_maximal| {
+        let units = instance.page_units(photos);
+        let pages = first_pages(&units, max_by_page, only_maximal, stop.max_pages());
+        if pages.len() == stop.max_pages() {
+            stop.skip();
+        }
+        pages
+    };
+    let waiting_pages = !waits.is_empty() || page < instance.pinned_pages();
+    let closes = instance.closes_spread(page);
+    let mut result: Option<Schedule> = None;
+    'chapters: for chapter in instance.next_chapters(current, started) {
+        // Get the photos that can go on the next page
+        let photos_ready: Vec<_> = graph
+            .ready_where(|photo| {
+                instance.pin(photo).allows(page)
+                    && !waiting(photo)
+                    && !spread_apart.contains(&photo)
+                    && instance.chapter(photo).is_none_or(|c| Some(c) == chapter)
+            })
+            .into_iter()
+            .collect();
+        if chapter.is_some()
+            && chapter != current
+            && photos_ready
+                .iter()
+                .all(|&photo| instance.chapter(photo).is_none())
+        {
+            // The chapter cannot start yet.
+            continue;
+        }
+        let pages = if let Some(spreads) = instance
+            .spreads
+            .as_ref()
+            .filter(|spreads| spreads.constrain())
+        {
+            // Try every page, but a blank spread only when waiting for a lag or a pin
+            // (a blank first page alone moves the next pages to the other side),
+            // and a group on the spread must be complete when it ends.
+            let empty_allowed = waiting_pages || !closes || !spread.is_empty() || page == 0;
+            let complete = |photos: &[u32]| {
+                spreads.together.iter().all(|group| {
+                    let left =
+                        |photo: &u32| graph.adj_list.contains_key(photo) && !photos.contains(photo);
+                    group.iter().all(left) || group.iter().all(|photo| !left(photo))
+                })
+            };
+            pages_of(&photos_ready, false)
+                .into_iter()
+                .filter(|photos| {
+                    (!photos.is_empty() || empty_allowed) && (!closes || complete(photos))
+                })
+                .collect()
+        } else if photos_ready.is_empty() && !waiting_pages {
+            // An empty page would not change anything.
+            continue;
+        // Case 2: All ready-to-use photos fit in the next page (and none are apart).
+        } else if instance.page_size(&photos_ready) <= max_by_page
+            && !instance.graph.has_apart_pair(&photos_ready)
+        {
+            vec![photos_ready]
+        // Case 3: Try all the ways to fill the next page.
+        } else {
+            pages_of(&photos_ready, true)
+        };
+        // Try first the pages with the photos heading the longest chains.
+        let mut pages = pages;
+        pages.sort_by_key(|photos| Reverse(photos.iter().map(|photo| tails[photo]).sum::<usize>()));
+        for photos in pages {
+            // The chapter stays open if it has started and has photos left.
+            let next_chapter = chapter.filter(|&c| {
+                (current == Some(c)
+                    || photos
+                        .iter()
+                        .any(|&photo| instance.chapter(photo) == Some(c)))
+                    && has_left(c, &photos)
+            });
+            let mut subgraph = graph.clone();
+            for &photo in &photos {
+                subgraph.remove(photo);
+            }
+            let waits = instance.next_waits(&waits, &photos);
+            let next_spread = if instance.spread_constrained() && !closes {
+                [spread, &photos].concat()
+            } else {
+                Vec::new()
+            };
+            // The rest must take fewer pages than with the best schedule so far.
+            let best = result.as_ref().map_or(bound, Vec::len);
+            let rest = schedule_feasible(
+                subgraph,
+                search,
+                page + 1,
+                waits,
+                next_chapter,
+                &next_spread,
+                best - 1,
+            );
+            if let Some(rest) = rest {
+                let mut schedule = vec![photos];
+                schedule.extend(rest);
+                result = Some(schedule);
+            }
+            if search.greedy || search.stop.stopped() {
+                break 'chapters;
+            }
+        }
+    }
+    result
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::Instance;
+    use crate::stop::Cancel;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_pruning() {
+        let mut edges = Vec::new();
+        for u in 1..7 {
+            for v in 1..u {
+                edges.push((u, v))
+            }
+        }
+        let instance = Instance::new(DependencyGraph::new(edges, 15), 3);
+        let mut statistics = Statistics::default();
+        let schedule = search(&instance, &mut statistics, &Stop::default(), None).unwrap();
+        assert_eq!(schedule.len(), 6);
+        assert!(instance.is_valid_schedule(&schedule));
+        // The greedy schedule follows the chain, which the bound of the root proves optimal.
+        let expected = Statistics {
+            nodes: 1,
+            pruned: 1,
+        };
+        assert_eq!(statistics, expected);
+        // Here the greedy schedule starts with photos 2, 3 and 4 and takes three pages.
+        let graph = DependencyGraph::builder(6)
+            .edge(1, 5)
+            .weak_edges(vec![(2, 5), (2, 4), (3, 4), (4, 2)])
+            .build()
+            .unwrap();
+        let instance = Instance::new(graph, 3);
+        let mut statistics = Statistics::default();
+        let schedule = search(&instance, &mut statistics, &Stop::default(), None).unwrap();
+        assert_eq!(schedule, vec![vec![1, 3, 6], vec![2, 4, 5]]);
+        let expected = Statistics {
+            nodes: 5,
+            pruned: 1,
+        };
+        assert_eq!(statistics, expected);
+    }
+    #[test]
+    fn test_stopped() {
+        let graph = DependencyGraph::builder(6)
+            .edge(1, 5)
+            .weak_edges(vec![(2, 5), (2, 4), (3, 4), (4, 2)])
+            .build()
+            .unwrap();
+        let instance = Instance::new(graph, 3);
+        let cancel = Cancel::default();
+        // Without any node, the search keeps the greedy schedule.
+        let stop = Stop::new(None, Some(0), &cancel);
+        let schedule = search(&instance, &mut Statistics::default(), &stop, None).unwrap();
+        assert!(stop.stopped());
+        assert_eq!(schedule.len(), 3);
+        assert!(instance.is_valid_schedule(&schedule));
+        // The five nodes of the whole search are enough.
+        let stop = Stop::new(None, Some(5), &cancel);
+        let schedule = search(&instance, &mut Statistics::default(), &stop, None).unwrap();
+        assert!(!stop.stopped());
+        assert_eq!(schedule.len(), 2);
+        // Cancelled, the search does not even descend, and keeps the incumbent.
+        cancel.cancel();
+        let stop = Stop::new(None, None, &cancel);
+        assert_eq!(
+            search(&instance, &mut Statistics::default(), &stop, None),
+            None
+        );
+        assert!(stop.stopped());
+        let incumbent = Some(schedule);
+        let result = search(
+            &instance,
+            &mut Statistics::default(),
+            &stop,
+            incumbent.clone(),
+        );
+        assert_eq!(result, incumbent);
+    }
+    #[test]
+    fn test_large_album_stopped() {
+        // Many photos are ready at once, too many pages to try them all.
+        let edges = (1..=150)
+            .map(|i| (2 * i - 1, 2 * i))
+            .chain([(1, 4), (3, 2)]);
+        let instance = Instance::new(DependencyGraph::new(edges.collect(), 300), 4);
+        let stop = Stop::new(None, Some(1000), &Cancel::default());
+        let schedule = search(&instance, &mut Statistics::default(), &stop, None).unwrap();
+        assert_eq!(schedule.len(), 75);
+        assert!(instance.is_valid_schedule(&schedule));
+        assert!(!stop.stopped() && !stop.ended());
+        // The time limit also stops the greedy descent.
+        let start = Instant::now();
+        let stop = Stop::new(Some(Duration::from_millis(100)), None, &Cancel::default());
+        let schedule = search(&instance, &mut Statistics::default(), &stop, None);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(schedule.is_none_or(|schedule| instance.is_valid_schedule(&schedule)));
+    }
+}
@@ -0,0 +1,644 @@
+use crate::bitmask::{BitmaskSolver, Schedules, MAX_PHOTOS};
+use crate::bound::min_pages_bound;
+use crate::capacity::Capacities;
+use crate::count::Count;
+use crate::error::SolveError;
+use crate::feedback::strongly_connected_components;
+use crate::heuristic;
+use crate::instance::{Instance, Schedule};
+use crate::random::Random;
+use crate::search;
+use crate::special::Class;
+use crate::stop::{Cancel, Stop};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// Algorithm used by a `Solver`.
+///
+/// Whatever the method, forests of plain edges and pages of two photos
+/// are solved by a polynomial algorithm, with any number of photos.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Memoized dynamic program on sets of photos (up to 64 photos).
+    Bitmask,
+    /// The original recursion, as a branch and bound: slower but simple.
+    Reference,
+    /// List scheduling by critical path followed by local improvement, fast on any
+    /// number of photos but not always optimal: compare with `Solver::lower_bound`.
+    /// The counts, `sweep` and `min_capacity` need the optimum and use `Bitmask` instead.
+    Heuristic,
+}
+
+/// Counters of the searches run by a `Solver`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Subproblems solved: the calls of the recursion with `Method::Reference`,
+    /// and the distinct states with `Method::Bitmask`.
+    pub nodes: u64,
+    /// Subproblems cut because their lower bound could not beat the best schedule
+    /// found (with `Method::Reference` only).
+    pub pruned: u64,
+}
+
+impl AddAssign for Statistics {
+    fn add_assign(&mut self, other: Statistics) {
+        self.nodes += other.nodes;
+        self.pruned += other.pruned;
+    }
+}
+
+/// Result of `Solver::solve`: the best schedule found within the limits of the search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    pub schedule: Schedule,
+    /// Lower bound on the minimum number of pages, the length of the schedule when optimal.
+    pub lower_bound: usize,
+    /// Whether the schedule is proven optimal, by a search that ended or by the lower bound.
+    pub optimal: bool,
+}
+
+impl Solution {
+    fn proven(schedule: Schedule) -> Self {
+        Self {
+            lower_bound: schedule.len(),
+            schedule,
+            optimal: true,
+        }
+    }
+}
+
+/// Entry point to compute the minimum number of pages of an instance.
+///
+/// ```
+/// use photo_ordering::{DependencyGraph, Instance, Solver};
+///
+/// let graph = DependencyGraph::builder(4).edges(vec![(2, 1), (3, 1), (1, 4)]).build().unwrap();
+/// let instance = Instance::new(graph, 2);
+/// assert_eq!(Solver::new(&instance).min_pages(), Ok(3));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Solver<'a> {
+    instance: &'a Instance,
+    method: Method,
+    page_multiple: usize,
+    statistics: Cell<Statistics>,
+    time_limit: Option<Duration>,
+    node_budget: Option<u64>,
+    cancel: Cancel,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(instance: &'a Instance) -> Self {
+        Self {
+            instance,
+            method: Method::Bitmask,
+            page_multiple: 1,
+            statistics: Cell::default(),
+            time_limit: None,
+            node_budget: None,
+            cancel: Cancel::default(),
+        }
+    }
+    /// Choose the algorithm (the default is `Method::Bitmask`).
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+    /// Round the number of pages up to a multiple of `multiple` (1 by default), for books
+    /// bound in signatures. The schedule then spreads the photos over the extra pages
+    /// so that the fullest page holds as few photos as possible.
+    pub fn page_multiple(mut self, multiple: usize) -> Self {
+        self.page_multiple = multiple;
+        self
+    }
+    /// Stop the search of `solve` after `limit`.
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+    /// Stop the search of `solve` after `nodes` nodes, counted as in `Statistics`.
+    pub fn node_budget(mut self, nodes: u64) -> Self {
+        self.node_budget = Some(nodes);
+        self
+    }
+    /// Return a handle to stop the searches of `solve` from another thread,
+    /// shared with the clones of this solver.
+    pub fn cancel_handle(&self) -> Cancel {
+        self.cancel.clone()
+    }
+    /// Return the counters of all the searches run by this solver so far.
+    pub fn statistics(&self) -> Statistics {
+        self.statistics.get()
+    }
+    /// Compute the minimum number of pages (rounded up to the page multiple).
+    pub fn min_pages(&self) -> Result<usize, SolveError> {
+        let n_pages = self.optimal_min_pages()?;
+        Ok(self.rounded(n_pages))
+    }
+    /// Compute the minimum number of pages (rounded up to the page multiple) when every
+    /// page holds the same number of photos, for each number in `max_by_page`.
+    ///
+    /// The numbers are solved in increasing order, and one is not solved at all when
+    /// the previous one already reaches its lower bound.
+    pub fn sweep(&self, max_by_page: &[usize]) -> Vec<Result<usize, SolveError>> {
+        let mut sorted = max_by_page.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let total_size = self.total_size();
+        // With room for all the photos on every page, only the constraints count.
+        let floor = self.uniform_min_pages(total_size).ok();
+        let mut results = BTreeMap::new();
+        let mut previous = None;
+        for max in sorted {
+            let bound = floor
+                .filter(|_| max > 0)
+                .map(|floor| floor.max(total_size.div_ceil(max)));
+            let n_pages = match previous {
+                Some(n_pages) if bound == Some(n_pages) => Ok(n_pages),
+                _ => self.uniform_min_pages(max),
+            };
+            if let Ok(n_pages) = n_pages {
+                previous = Some(n_pages);
+            }
+            results.insert(max, n_pages.map(|n_pages| self.rounded(n_pages)));
+        }
+        max_by_page.iter().map(|max| results[max].clone()).collect()
+    }
+    /// Return the smallest number of photos by page, the same on every page, for which
+    /// the photos fit on `n_pages` pages (rounded up to the page multiple), or `None`
+    /// if they never do.
+    pub fn min_capacity(&self, n_pages: usize) -> Result<Option<usize>, SolveError> {
+        let total_size = self.total_size();
+        if self.rounded(self.uniform_min_pages(total_size)?) > n_pages {
+            return Ok(None);
+        }
+        let fits = |max| {
+            self.uniform_min_pages(max)
+                .is_ok_and(|n| self.rounded(n) <= n_pages)
+        };
+        Ok(Some(smallest(1, total_size, fits)))
+    }
+    /// Return a lower bound on the minimum number of pages (rounded up to the page multiple),
+    /// from the longest chain of photos and the room they take, to see how far
+    /// a heuristic schedule can be from the optimum.
+    pub fn lower_bound(&self) -> Result<usize, SolveError> {
+        self.check()?;
+        let n_pages = match self.instance.independent_chapters() {
+            Some(chapters) => chapters
+                .iter()
+                .map(|photos| min_pages_bound(&self.instance.restricted(photos)))
+                .sum(),
+            None => min_pages_bound(self.instance),
+        };
+        Ok(self.rounded(n_pages))
+    }
+    /// Compute a schedule with the minimum number of pages
+    /// (rounded up to the page multiple, the photos being spread over them).
+    pub fn schedule(&self) -> Result<Schedule, SolveError> {
+        if self.page_multiple == 1 {
+            return self.optimal_schedule();
+        }
+        let n_pages = self.min_pages()?;
+        let with_capacities = |capacities: Capacities| {
+            let mut instance = self.instance.clone();
+            instance.capacities = capacities;
+            instance
+        };
+        let fits = |capacities: Capacities| {
+            let instance = with_capacities(capacities);
+            let solver = Solver::new(&instance).method(self.method);
+            let fits = solver.min_pages().is_ok_and(|n| n <= n_pages);
+            self.record(solver.statistics());
+            fits
+        };
+        // Look for the smallest number of photos by page that still fits in `n_pages`,
+        // then for the fewest pages holding that many.
+        let capacities = &self.instance.capacities;
+        let max_by_page = smallest(1, capacities.max(), |max| fits(capacities.capped(max)));
+        let mut spread = capacities.capped(max_by_page);
+        // The photos too large for the less full pages go on the full ones.
+        if max_by_page > 1 && fits(capacities.fuller_first(n_pages, max_by_page)) {
+            let n_full = smallest(0, n_pages, |n_full| {
+                fits(capacities.fuller_first(n_full, max_by_page))
+            });
+            spread = capacities.fuller_first(n_full, max_by_page);
+        }
+        let instance = with_capacities(spread);
+        let solver = Solver::new(&instance).method(self.method);
+        let schedule = solver.schedule();
+        self.record(solver.statistics());
+        let mut schedule = schedule?;
+        schedule.resize(n_pages, Vec::new());
+        Ok(schedule)
+    }
+    /// Compute a schedule with as few pages as possible (without rounding to the page multiple)
+    /// before the time limit, the node budget or the cancellation stop the search, with a lower
+    /// bound on the minimum number of pages and whether the schedule is proven optimal.
+    ///
+    /// A stopped search keeps the best of the schedules it found and the heuristic one.
+    /// Without limits, this is `schedule` with the proof of optimality.
+    ///
+    /// Independent chapters are solved one by one within the same limits: once a limit
+    /// is reached, the chapters left keep their heuristic schedule. `optimal` and
+    /// `lower_bound` then cover the whole instance, which is proven optimal when every
+    /// chapter is.
+    ///
+    /// ```
+    /// use photo_ordering::{DependencyGraph, Instance, Solver};
+    ///
+    /// // Each photo after the photos four and five places before it.
+    /// let edges = (5..30).flat_map(|i| [(i - 4, i + 1), (i - 3, i + 1)]);
+    /// let graph = DependencyGraph::builder(30).edges(edges).build().unwrap();
+    /// let instance = Instance::new(graph, 3);
+    /// let solver = Solver::new(&instance).node_budget(100);
+    /// let solution = solver.solve().unwrap();
+    /// // The search stops on its budget, but the lower bound proves the schedule optimal.
+    /// assert_eq!(solver.statistics().nodes, 100);
+    /// assert!(solution.optimal);
+    /// assert_eq!((solution.schedule.len(), solution.lower_bound), (10, 10));
+    /// ```
+    pub fn solve(&self) -> Result<Solution, SolveError> {
+        self.check()?;
+        let stop = Stop::new(self.time_limit, self.node_budget, &self.cancel);
+        let chapters = match self.instance.independent_chapters() {
+            Some(chapters) => chapters,
+            None => return self.solve_within(self.instance, &stop),
+        };
+        // The chapters share the limits, and the whole schedule is optimal if each part is.
+        let mut solution = Solution::proven(Vec::new());
+        for photos in chapters {
+            stop.restart();
+            let part = self.solve_within(&self.instance.restricted(&photos), &stop)?;
+            solution.schedule.extend(renumbered(part.schedule, &photos));
+            solution.lower_bound += part.lower_bound;
+            solution.optimal &= part.optimal;
+        }
+        Ok(solution)
+    }
+    /// Iterate over the distinct schedules with the minimum number of pages, the order
+    /// of the photos on a page not mattering. They are always enumerated by the bitmask
+    /// method, and without rounding to the page multiple.
+    pub fn schedules(&self) -> Result<Schedules<'a>, SolveError> {
+        self.check_whole()?;
+        BitmaskSolver::new(self.instance)
+            .schedules()
+            .ok_or_else(|| self.unsatisfiable(self.instance))
+    }
+    /// Draw one of the distinct schedules with the minimum number of pages (without rounding
+    /// to the page multiple) uniformly at random, the same `seed` giving the same schedule.
+    pub fn sample(&self, seed: u64) -> Result<Schedule, SolveError> {
+        self.check_whole()?;
+        BitmaskSolver::new(self.instance)
+            .sample(&mut Random::new(seed))
+            .ok_or_else(|| self.unsatisfiable(self.instance))
+    }
+    /// Count the distinct schedules with the minimum number of pages (without rounding
+    /// to the page multiple), as `schedules` would enumerate them.
+    pub fn count_optimal(&self) -> Result<Count, SolveError> {
+        let solver = Solver::new(self.instance).method(self.exact_method());
+        let n_pages = solver.optimal_min_pages();
+        self.record(solver.statistics());
+        let n_pages = n_pages?;
+        self.count_within(n_pages)
+    }
+    /// Count the distinct ways to place the photos on the first `n_pages` pages,
+    /// some of the last ones possibly staying empty.
+    pub fn count_within(&self, n_pages: usize) -> Result<Count, SolveError> {
+        self.check_whole()?;
+        Ok(BitmaskSolver::new(self.instance).count_schedules(n_pages))
+    }
+    /// Round a number of pages up to the page multiple.
+    fn rounded(&self, n_pages: usize) -> usize {
+        n_pages.div_ceil(self.page_multiple) * self.page_multiple
+    }
+    /// Return the number of slots taken by all the photos, at least 1.
+    fn total_size(&self) -> usize {
+        self.instance.sizes.iter().sum::<usize>().max(1)
+    }
+    /// Compute the minimum number of pages (not rounded) when every page
+    /// holds `max_by_page` photos.
+    fn uniform_min_pages(&self, max_by_page: usize) -> Result<usize, SolveError> {
+        let mut instance = self.instance.clone();
+        instance.capacities = Capacities::uniform(max_by_page);
+        let solver = Solver::new(&instance)
+            .method(self.exact_method())
+            .page_multiple(self.page_multiple);
+        let n_pages = solver.optimal_min_pages();
+        self.record(solver.statistics());
+        n_pages
+    }
+    /// Return the method of the searches that must find the optimum.
+    fn exact_method(&self) -> Method {
+        match self.method {
+            Method::Heuristic => Method::Bitmask,
+            method => method,
+        }
+    }
+    /// Add the counters of a search to those of this solver.
+    fn record(&self, statistics: Statistics) {
+        let mut total = self.statistics.get();
+        total += statistics;
+        self.statistics.set(total);
+    }
+    fn optimal_min_pages(&self) -> Result<usize, SolveError> {
+        self.check()?;
+        if let Some(chapters) = self.instance.independent_chapters() {
+            let mut n_pages = 0;
+            for photos in chapters {
+                n_pages += self.solve_min_pages(&self.instance.restricted(&photos))?;
+            }
+            return Ok(n_pages);
+        }
+        self.solve_min_pages(self.instance)
+    }
+    fn optimal_schedule(&self) -> Result<Schedule, SolveError> {
+        self.check()?;
+        if let Some(chapters) = self.instance.independent_chapters() {
+            // Solve the chapters one by one and put their pages one after the other.
+            let mut schedule = Vec::new();
+            for photos in chapters {
+                let pages = self.solve_schedule(&self.instance.restricted(&photos))?;
+                schedule.extend(renumbered(pages, &photos));
+            }
+            return Ok(schedule);
+        }
+        self.solve_schedule(self.instance)
+    }
+    fn solve_min_pages(&self, instance: &Instance) -> Result<usize, SolveError> {
+        if let Some(class) = Class::of(instance) {
+            return Ok(class.schedule(instance).len());
+        }
+        let n_pages = match self.method {
+            Method::Bitmask => {
+                let mut solver = BitmaskSolver::new(instance);
+                let n_pages = solver.min_pages();
+                self.record_states(&solver);
+                n_pages
+            }
+            Method::Reference => self
+                .search(instance, &Stop::default(), None)
+                .map(|schedule| schedule.len()),
+            Method::Heuristic => {
+                let schedule = heuristic::schedule(instance);
+                return schedule
+                    .map(|schedule| schedule.len())
+                    .ok_or(SolveError::HeuristicFailed);
+            }
+        };
+        n_pages.ok_or_else(|| self.unsatisfiable(instance))
+    }
+    fn solve_schedule(&self, instance: &Instance) -> Result<Schedule, SolveError> {
+        if let Some(class) = Class::of(instance) {
+            return Ok(class.schedule(instance));
+        }
+        let schedule = match self.method {
+            Method::Bitmask => {
+                let mut solver = BitmaskSolver::new(instance);
+                let schedule = solver.schedule();
+                self.record_states(&solver);
+                schedule
+            }
+            Method::Reference => self.search(instance, &Stop::default(), None),
+            Method::Heuristic => {
+                return heuristic::schedule(instance).ok_or(SolveError::HeuristicFailed);
+            }
+        };
+        schedule.ok_or_else(|| self.unsatisfiable(instance))
+    }
+    /// Compute a schedule until `stop` is reached.
+    fn solve_within(&self, instance: &Instance, stop: &Stop) -> Result<Solution, SolveError> {
+        if let Some(class) = Class::of(instance) {
+            return Ok(Solution::proven(class.schedule(instance)));
+        }
+        let schedule = match self.method {
+            Method::Bitmask => {
+                let mut solver = BitmaskSolver::new(instance).stop(stop);
+                let schedule = solver.schedule();
+                self.record_states(&solver);
+                schedule
+            }
+            Method::Reference => {
+                // The heuristic schedule is the one to beat, unless it reaches the bound.
+                let incumbent = heuristic::schedule(instance);
+                if let Some(schedule) = incumbent
+                    .as_ref()
+                    .filter(|schedule| schedule.len() == min_pages_bound(instance))
+                {
+                    return Ok(Solution::proven(schedule.clone()));
+                }
+                self.search(instance, stop, incumbent)
+            }
+            Method::Heuristic => None,
+        };
+        if self.method != Method::Heuristic && stop.ended() {
+            return schedule
+                .map(Solution::proven)
+                .ok_or_else(|| self.unsatisfiable(instance));
+        }
+        let schedule = schedule
+            .into_iter()
+            .chain(heuristic::schedule(instance))
+            .min_by_key(Vec::len);
+        let lower_bound = min_pages_bound(instance);
+        match schedule {
+            Some(schedule) => Ok(Solution {
+                optimal: schedule.len() == lower_bound,
+                schedule,
+                lower_bound,
+            }),
+            None if self.method == Method::Heuristic => Err(SolveError::HeuristicFailed),
+            None => Err(SolveError::Stopped),
+        }
+    }
+    /// Run the branch and bound until `stop` is reached, looking for a schedule shorter
+    /// than `incumbent` if any, and record its counters.
+    fn search(
+        &self,
+        instance: &Instance,
+        stop: &Stop,
+        incumbent: Option<Schedule>,
+    ) -> Option<Schedule> {
+        let mut statistics = Statistics::default();
+        let schedule = search::search(instance, &mut statistics, stop, incumbent);
+        self.record(statistics);
+        schedule
+    }
+    /// Record the states solved by a bitmask solver.
+    fn record_states(&self, solver: &BitmaskSolver) {
+        self.record(Statistics {
+            nodes: solver.n_states() as u64,
+            pruned: 0,
+        });
+    }
+    /// Explain why a feasible instance has no schedule: the pins,
+    /// unless it has a schedule without its spread constraints or its chapters.
+    fn unsatisfiable(&self, instance: &Instance) -> SolveError {
+        if instance.spread_constrained() {
+            let mut without_spreads = instance.clone();
+            without_spreads.spreads = None;
+            return match self.solve_min_pages(&without_spreads) {
+                Ok(_) => SolveError::SpreadsUnsatisfiable,
+                Err(error) => error,
+            };
+        }
+        if instance.chapters.is_empty() {
+            return SolveError::PinsUnsatisfiable;
+        }
+        let mut without_chapters = instance.clone();
+        without_chapters.chapters.clear();
+        match self.solve_min_pages(&without_chapters) {
+            Ok(_) => SolveError::ChaptersUnsatisfiable,
+            Err(error) => error,
+        }
+    }
+    /// Reject the infeasible instances and those the bitmask method cannot handle
+    /// without splitting them into chapters, whatever the chosen method.
+    fn check_whole(&self) -> Result<(), SolveError> {
+        self.check()?;
+        let n_photos = self.instance.graph.count_vertices();
+        if n_photos > MAX_PHOTOS {
+            return Err(SolveError::TooManyPhotos {
+                n_photos,
+                max: MAX_PHOTOS,
+            });
+        }
+        Ok(())
+    }
+    /// Reject the infeasible instances and those the chosen method cannot handle.
+    fn check(&self) -> Result<(), SolveError> {
+        if self.instance.capacities.min() == 0 {
+            return Err(SolveError::ZeroCapacity);
+        }
+        if self.page_multiple == 0 {
+            return Err(SolveError::ZeroPageMultiple);
+        }
+        let n_photos = self.instance.graph.count_vertices();
+        if self.instance.sizes.len() != n_photos {
+            return Err(SolveError::SizeCount {
+                n_photos,
+                n_sizes: self.instance.sizes.len(),
+            });
+        }
+        for (&photo, &pin) in &self.instance.pins {
+            if photo == 0 || photo as usize > n_photos || !pin.is_valid() {
+                return Err(SolveError::InvalidPin { photo, pin });
+            }
+        }
+        if let Some(&photo) = self
+            .instance
+            .chapters
+            .keys()
+            .find(|&&photo| photo == 0 || photo as usize > n_photos)
+        {
+            return Err(SolveError::InvalidChapter { photo, n_photos });
+        }
+        let spreads = self.instance.spreads.as_ref();
+        if let Some(photo) = spreads
+            .into_iter()
+            .flat_map(|spreads| spreads.photos())
+            .find(|&photo| photo == 0 || photo as usize > n_photos)
+        {
+            return Err(SolveError::InvalidSpread { photo, n_photos });
+        }
+        if spreads.is_some_and(|spreads| spreads.apart.iter().any(|&(u, v)| u == v)) {
+            return Err(SolveError::SpreadsUnsatisfiable);
+        }
+        // Every photo must fit on the pages repeated until the end of the book.
+        let largest_page = self.instance.capacities.max_repeated();
+        for photo in 1..=n_photos as u32 {
+            let size = self.instance.size(photo);
+            if size == 0 || size > largest_page {
+                return Err(SolveError::InvalidSize { photo, size });
+            }
+        }
+        if let Some((photos, chain)) = self.instance.graph.find_separated_group() {
+            return Err(SolveError::SeparatedGroup { photos, chain });
+        }
+        if let Some(cycle) = self.instance.graph.find_cycle() {
+            return Err(SolveError::Cyclic { cycle });
+        }
+        // Photos linked by a cycle of weak edges share a page (and so a spread).
+        let spread_apart = spreads
+            .into_iter()
+            .flat_map(|spreads| spreads.apart.iter().copied());
+        let apart_pairs: Vec<_> = self
+            .instance
+            .graph
+            .apart_pairs()
+            .chain(spread_apart)
+            .collect();
+        // Those with a weak edge to a last photo share the last page.
+        let mut groups =
+            strongly_connected_components(&self.instance.graph.with_end().weak_adj_list);
+        for photos in &mut groups {
+            photos.retain(|&photo| photo != 0);
+        }
+        let mut group = BTreeMap::new();
+        for (i, photos) in groups.iter().enumerate() {
+            group.extend(photos.iter().map(|&photo| (photo, i)));
+        }
+        // The first apart pair inside each group.
+        let mut apart_inside = BTreeMap::new();
+        for &(u, v) in &apart_pairs {
+            if group.get(&u).is_some_and(|i| group.get(&v) == Some(i)) {
+                apart_inside.entry(group[&u]).or_insert((u, v));
+            }
+        }
+        for (i, photos) in groups.into_iter().enumerate() {
+            if let Some(&pair) = apart_inside.get(&i) {
+                return Err(SolveError::ApartTogether { pair, photos });
+            }
+            let size = self.instance.page_size(&photos);
+            if photos.len() > 1 && size > largest_page {
+                return Err(SolveError::GroupTooLarge {
+                    photos,
+                    size,
+                    capacity: largest_page,
+                });
+            }
+        }
+        // Independent chapters are solved one by one, and special classes by their own algorithm.
+        let largest = match self.instance.independent_chapters() {
+            Some(chapters) => chapters
+                .iter()
+                .filter(|photos| Class::of(&self.instance.restricted(photos)).is_none())
+                .map(Vec::len)
+                .max()
+                .unwrap_or(0),
+            None if Class::of(self.instance).is_some() => 0,
+            None => n_photos,
+        };
+        if self.method == Method::Bitmask && largest > MAX_PHOTOS {
+            return Err(SolveError::TooManyPhotos {
+                n_photos: largest,
+                max: MAX_PHOTOS,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Number the photos of the schedule of a chapter restricted to `photos` as in the whole instance.
+fn renumbered(schedule: Schedule, photos: &[u32]) -> impl Iterator<Item = Vec<u32>> + '_ {
+    schedule.into_iter().map(move |page| {
+        page.into_iter()
+            .map(|photo| photos[photo as usize - 1])
+            .collect()
+    })
+}
+
+/// Return the smallest number in `low..=high` satisfying `holds`,
+/// which holds for `high` and for every number above one for which it holds.
+fn smallest(mut low: usize, mut high: usize, holds: impl Fn(usize) -> bool) -> usize {
+    while low < high {
+        let middle = (low + high) / 2;
+        if holds(middle) {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    high
+}
@@ -0,0 +1,309 @@
+//! Polynomial algorithms for instances of special structure, used instead of the
+//! exponential search of the solvers.
+//!
+//! They apply to photos of size 1 on pages all holding the same number of photos,
+//! with plain edges only: no weak edge, lag, apart pair, pin, last photo, chapter nor
+//! spread constraint.
+//! Hu's algorithm is optimal when every photo has at most one successor (an in-forest),
+//! and so when every photo has at most one predecessor (an out-forest) by scheduling
+//! the reversed graph backwards. The Coffman–Graham algorithm is optimal for any graph
+//! when the pages hold two photos. Both fill each page with the ready photos of highest
+//! priority and only differ by the priority.
+
+use crate::instance::{Instance, Schedule};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Structure of an instance with a polynomial algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Class {
+    /// Every photo has at most one successor.
+    InForest,
+    /// Every photo has at most one predecessor.
+    OutForest,
+    /// Every page holds two photos.
+    TwoByPage,
+}
+
+impl Class {
+    /// Return the class of an acyclic instance, if it has one.
+    pub fn of(instance: &Instance) -> Option<Class> {
+        let graph = &instance.graph;
+        let max_by_page = instance.capacities.as_uniform()?;
+        let plain = instance.sizes.iter().all(|&size| size == 1)
+            && graph.weak_edges().next().is_none()
+            && graph.last.is_empty()
+            && graph.lags.is_empty()
+            && graph.apart.is_empty()
+            && instance.pins.is_empty()
+            && instance.chapters.is_empty()
+            && !instance.spread_constrained();
+        if !plain {
+            return None;
+        }
+        let successors = successors(instance);
+        if successors.iter().all(|next| next.len() <= 1) {
+            Some(Class::InForest)
+        } else if reversed(&successors)
+            .iter()
+            .all(|previous| previous.len() <= 1)
+        {
+            Some(Class::OutForest)
+        } else if max_by_page == 2 {
+            Some(Class::TwoByPage)
+        } else {
+            None
+        }
+    }
+    /// Return an optimal schedule of an instance of this class.
+    pub fn schedule(self, instance: &Instance) -> Schedule {
+        let max_by_page = instance.capacities.as_uniform().unwrap();
+        let successors = successors(instance);
+        match self {
+            Class::InForest => list_schedule(&successors, &levels(&successors), max_by_page),
+            Class::OutForest => {
+                let predecessors = reversed(&successors);
+                let mut schedule =
+                    list_schedule(&predecessors, &levels(&predecessors), max_by_page);
+                schedule.reverse();
+                schedule
+            }
+            Class::TwoByPage => list_schedule(&successors, &labels(&successors), max_by_page),
+        }
+    }
+}
+
+/// Return the distinct successors of each photo, photo `v` being at index `v - 1`.
+fn successors(instance: &Instance) -> Vec<Vec<usize>> {
+    instance
+        .graph
+        .adj_list
+        .values()
+        .map(|neighbourhood| {
+            let mut next: Vec<usize> = neighbourhood.iter().map(|&v| v as usize - 1).collect();
+            next.sort_unstable();
+            next.dedup();
+            next
+        })
+        .collect()
+}
+
+/// Return the predecessors of each photo.
+fn reversed(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (u, next) in successors.iter().enumerate() {
+        for &v in next {
+            predecessors[v].push(u);
+        }
+    }
+    predecessors
+}
+
+/// Return the photos in an order where every edge goes forward.
+fn topological_order(successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut n_predecessors = vec![0; successors.len()];
+    for &v in successors.iter().flatten() {
+        n_predecessors[v] += 1;
+    }
+    let mut order: Vec<usize> = (0..successors.len())
+        .filter(|&v| n_predecessors[v] == 0)
+        .collect();
+    let mut i = 0;
+    while let Some(&u) = order.get(i) {
+        for &v in &successors[u] {
+            n_predecessors[v] -= 1;
+            if n_predecessors[v] == 0 {
+                order.push(v);
+            }
+        }
+        i += 1;
+    }
+    order
+}
+
+/// Hu's priority: the number of photos of the longest path starting at each photo.
+fn levels(successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut levels = vec![0; successors.len()];
+    for u in topological_order(successors).into_iter().rev() {
+        levels[u] = 1 + successors[u].iter().map(|&v| levels[v]).max().unwrap_or(0);
+    }
+    levels
+}
+
+/// Coffman–Graham priority: labels from 1 given in turn to the photos whose successors
+/// are all labelled, choosing the photo whose successor labels in decreasing order come
+/// first lexicographically. The edges implied by others do not change the schedule,
+/// so the graph is not reduced first.
+fn labels(successors: &[Vec<usize>]) -> Vec<usize> {
+    let n_photos = successors.len();
+    let predecessors = reversed(successors);
+    let mut n_unlabelled: Vec<usize> = successors.iter().map(Vec::len).collect();
+    let mut labels = vec![0; n_photos];
+    // The photos whose successors are all labelled, by their successor labels.
+    let mut ready: BinaryHeap<_> = (0..n_photos)
+        .filter(|&u| n_unlabelled[u] == 0)
+        .map(|u| Reverse((Vec::new(), u)))
+        .collect();
+    for label in 1..=n_photos {
+        let Reverse((_, u)) = ready.pop().unwrap();
+        labels[u] = label;
+        for &w in &predecessors[u] {
+            n_unlabelled[w] -= 1;
+            if n_unlabelled[w] == 0 {
+                let mut next: Vec<usize> = successors[w].iter().map(|&v| labels[v]).collect();
+                next.sort_unstable_by(|a, b| b.cmp(a));
+                ready.push(Reverse((next, w)));
+            }
+        }
+    }
+    labels
+}
+
+/// Fill each page with the `max_by_page` ready photos of highest `priority`
+/// (the first photos on ties) until every photo is placed.
+fn list_schedule(successors: &[Vec<usize>], priority: &[usize], max_by_page: usize) -> Schedule {
+    let mut n_predecessors = vec![0; successors.len()];
+    for &v in successors.iter().flatten() {
+        n_predecessors[v] += 1;
+    }
+    let mut ready: BinaryHeap<_> = (0..successors.len())
+        .filter(|&v| n_predecessors[v] == 0)
+        .map(|v| (priority[v], Reverse(v)))
+        .collect();
+    let mut schedule = Vec::new();
+    while !ready.is_empty() {
+        let mut page = Vec::new();
+        while page.len() < max_by_page {
+            match ready.pop() {
+                Some((_, Reverse(u))) => page.push(u),
+                None => break,
+            }
+        }
+        // The photos released by this page only go on the next ones.
+        for &u in &page {
+            for &v in &successors[u] {
+                n_predecessors[v] -= 1;
+                if n_predecessors[v] == 0 {
+                    ready.push((priority[v], Reverse(v)));
+                }
+            }
+        }
+        let mut page: Vec<u32> = page.into_iter().map(|u| u as u32 + 1).collect();
+        page.sort_unstable();
+        schedule.push(page);
+    }
+    schedule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::DependencyGraph;
+    use crate::random::Random;
+    use crate::reference;
+
+    /// Check the schedule of the class found against the reference recursion.
+    fn check_against_reference(instance: &Instance, class: Class) {
+        assert_eq!(Class::of(instance), Some(class));
+        let schedule = class.schedule(instance);
+        assert!(instance.is_valid_schedule(&schedule));
+        assert_eq!(Some(schedule.len()), reference::min_pages(instance));
+    }
+    /// A random forest, each photo pointing to a later one or to none.
+    fn random_forest(random: &mut Random, n_photos: usize) -> Vec<(u32, u32)> {
+        (1..n_photos as u32)
+            .filter_map(|u| {
+                let v = u + 1 + random.below((n_photos as u32 - u + 1) as u64) as u32;
+                (v <= n_photos as u32).then_some((u, v))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classes() {
+        let star: Vec<_> = (1..12).map(|i| (i, 12)).collect();
+        let g = DependencyGraph::new(star.clone(), 12);
+        assert_eq!(Class::of(&Instance::new(g, 3)), Some(Class::InForest));
+        let g = DependencyGraph::new(star.iter().map(|&(u, v)| (v, u)).collect(), 12);
+        assert_eq!(Class::of(&Instance::new(g, 3)), Some(Class::OutForest));
+        let diamond = DependencyGraph::new(vec![(1, 2), (1, 3), (2, 4), (3, 4)], 4);
+        assert_eq!(Class::of(&Instance::new(diamond.clone(), 3)), None);
+        let instance = Instance::new(diamond, 2);
+        assert_eq!(Class::of(&instance), Some(Class::TwoByPage));
+        let mut sized = instance.clone();
+        sized.sizes[0] = 2;
+        assert_eq!(Class::of(&sized), None);
+        let mut chapters = instance;
+        chapters.chapters.insert(1, 0);
+        assert_eq!(Class::of(&chapters), None);
+    }
+    #[test]
+    fn test_forests_against_reference() {
+        let mut random = Random::new(3);
+        for _ in 0..40 {
+            let n_photos = 4 + random.below(9) as usize;
+            let edges = random_forest(&mut random, n_photos);
+            let backwards: Vec<_> = edges.iter().map(|&(u, v)| (v, u)).collect();
+            for max_by_page in 2..5 {
+                let instance =
+                    Instance::new(DependencyGraph::new(edges.clone(), n_photos), max_by_page);
+                check_against_reference(&instance, Class::InForest);
+                let instance = Instance::new(
+                    DependencyGraph::new(backwards.clone(), n_photos),
+                    max_by_page,
+                );
+                if Class::of(&instance) == Some(Class::OutForest) {
+                    check_against_reference(&instance, Class::OutForest);
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_two_by_page_against_reference() {
+        let mut random = Random::new(5);
+        for _ in 0..60 {
+            let n_photos = 4 + random.below(9) as usize;
+            let mut edges = Vec::new();
+            for _ in 0..n_photos + random.below(n_photos as u64) as usize {
+                let u = 1 + random.below(n_photos as u64) as u32;
+                let v = 1 + random.below(n_photos as u64) as u32;
+                if u < v {
+                    edges.push((u, v));
+                }
+            }
+            let instance = Instance::new(DependencyGraph::new(edges.clone(), n_photos), 2);
+            if let Some(class) = Class::of(&instance) {
+                check_against_reference(&instance, class);
+            }
+            // The edges implied by the others change nothing.
+            let mut closure = vec![Vec::new(); n_photos + 1];
+            for u in (1..=n_photos as u32).rev() {
+                for &(_, v) in edges.iter().filter(|&&(w, _)| w == u) {
+                    let after = [vec![v], closure[v as usize].clone()].concat();
+                    closure[u as usize].extend(after);
+                }
+            }
+            let edges = (1..=n_photos as u32)
+                .flat_map(|u| closure[u as usize].iter().map(move |&v| (u, v)))
+                .collect();
+            let instance = Instance::new(DependencyGraph::new(edges, n_photos), 2);
+            check_against_reference(&instance, Class::of(&instance).unwrap());
+        }
+    }
+    #[test]
+    fn test_large_two_by_page() {
+        let mut random = Random::new(7);
+        let n_photos = 20_000;
+        let edges = (0..3 * n_photos)
+            .filter_map(|_| {
+                let u = 1 + random.below(n_photos as u64) as u32;
+                let v = 1 + random.below(n_photos as u64) as u32;
+                (u < v).then_some((u, v))
+            })
+            .collect();
+        let instance = Instance::new(DependencyGraph::new(edges, n_photos), 2);
+        assert_eq!(Class::of(&instance), Some(Class::TwoByPage));
+        let schedule = Class::TwoByPage.schedule(&instance);
+        assert!(instance.is_valid_schedule(&schedule));
+    }
+}
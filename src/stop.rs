@@ -0,0 +1,159 @@
+//! Limits on the exact searches: a time limit, a budget of nodes and a handle to cancel
+//! them from another thread.
+//!
+//! The searches check their `Stop` at each node. Once it is reached, every node left
+//! returns at once, and the search unwinds with the best schedule found so far.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of pages a search within limits tries at most on each node.
+const MAX_PAGES: usize = 1000;
+
+/// Handle to cancel the searches of a `Solver`, possibly from another thread.
+///
+/// ```
+/// use photo_ordering::{DependencyGraph, Instance, Method, Solver};
+/// use std::time::Duration;
+///
+/// // Twenty photos taking four slots each, two by page: the search cannot quickly
+/// // prove that eight pages are out of reach.
+/// let graph = DependencyGraph::builder(20).build().unwrap();
+/// let mut instance = Instance::new(graph, 10);
+/// instance.sizes = vec![4; 20];
+/// let solver = Solver::new(&instance).method(Method::Reference);
+/// let cancel = solver.cancel_handle();
+/// let canceller = std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(100));
+///     cancel.cancel();
+/// });
+/// // The search runs until cancelled, then returns the best schedule found so far.
+/// let solution = solver.solve().unwrap();
+/// canceller.join().unwrap();
+/// assert!(solver.statistics().nodes > 0);
+/// assert!(!solution.optimal);
+/// assert_eq!((solution.schedule.len(), solution.lower_bound), (10, 8));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Cancel {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancel {
+    /// Stop the searches running or to come, which then return the best schedule found so far.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// When a search must stop, the time limit counting from the creation of the `Stop`.
+/// The default never stops.
+#[derive(Debug, Default)]
+pub(crate) struct Stop {
+    deadline: Option<Instant>,
+    /// Number of nodes the search may visit.
+    budget: Option<u64>,
+    cancel: Cancel,
+    /// Number of nodes visited so far.
+    nodes: Cell<u64>,
+    /// Whether a limit was reached.
+    stopped: Cell<bool>,
+    /// Whether the search left pages out.
+    skipped: Cell<bool>,
+}
+
+impl Stop {
+    pub fn new(time_limit: Option<Duration>, budget: Option<u64>, cancel: &Cancel) -> Self {
+        Self {
+            // A time limit too far in the future is no limit.
+            deadline: time_limit.and_then(|limit| Instant::now().checked_add(limit)),
+            budget,
+            cancel: cancel.clone(),
+            ..Self::default()
+        }
+    }
+    /// Count a new node and return whether the search must stop instead of visiting it.
+    pub fn node(&self) -> bool {
+        if !self.stopped.get() {
+            let nodes = self.nodes.get() + 1;
+            self.nodes.set(nodes);
+            self.stopped
+                .set(self.budget.is_some_and(|budget| nodes > budget));
+        }
+        self.interrupted()
+    }
+    /// Return whether the time limit or the cancellation stops the search,
+    /// without counting a node.
+    pub fn interrupted(&self) -> bool {
+        if !self.stopped.get() {
+            let stopped = self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+                || self.cancel.is_cancelled();
+            self.stopped.set(stopped);
+        }
+        self.stopped.get()
+    }
+    /// Return the number of pages to try at most on each node: all of them without
+    /// a time limit or a node budget, which too many pages could exceed at a single node.
+    pub fn max_pages(&self) -> usize {
+        if self.deadline.is_some() || self.budget.is_some() {
+            MAX_PAGES
+        } else {
+            usize::MAX
+        }
+    }
+    /// Record that the search left pages out, so that it cannot prove its schedule optimal.
+    pub fn skip(&self) {
+        self.skipped.set(true);
+    }
+    /// Start the search of another part of the instance within the same limits:
+    /// the pages left out before do not count against it, but a limit reached still stops it.
+    pub fn restart(&self) {
+        self.skipped.set(false);
+    }
+    /// Return whether the search ended without leaving pages out,
+    /// so that its schedule is optimal.
+    pub fn ended(&self) -> bool {
+        !self.stopped.get() && !self.skipped.get()
+    }
+    /// Return whether a limit was reached, so that the search did not end.
+    pub fn stopped(&self) -> bool {
+        self.stopped.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop() {
+        let cancel = Cancel::default();
+        let stop = Stop::new(None, Some(2), &cancel);
+        assert!(!stop.node() && !stop.node() && !stop.stopped());
+        assert!(stop.node() && stop.stopped());
+        let unlimited = Stop::new(Some(Duration::MAX), None, &cancel);
+        assert!((0..100).all(|_| !unlimited.node()));
+        cancel.clone().cancel();
+        assert!(cancel.is_cancelled() && unlimited.node());
+        assert!(Stop::new(Some(Duration::ZERO), None, &Cancel::default()).node());
+        assert!(!Stop::default().node());
+        let skipped = Stop::new(None, Some(10), &Cancel::default());
+        assert_eq!(skipped.max_pages(), MAX_PAGES);
+        skipped.skip();
+        assert!(!skipped.node() && !skipped.interrupted() && !skipped.ended());
+        skipped.restart();
+        assert!(skipped.ended());
+        let stopped = Stop::new(None, Some(0), &Cancel::default());
+        assert!(stopped.node());
+        stopped.restart();
+        assert!(stopped.stopped() && !stopped.ended());
+        assert_eq!(Stop::default().max_pages(), usize::MAX);
+    }
+}
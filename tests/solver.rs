@@ -0,0 +1,537 @@
+use photo_ordering::{
+    parse, read_file, Capacities, Count, DependencyGraph, GraphError, Instance, Method, ParseError,
+    Pin, SolveError, Solver, Statistics,
+};
+use std::time::Duration;
+
+fn instance(edges: Vec<(u32, u32)>, n_photos: usize, max_by_page: usize) -> Instance {
+    let graph = DependencyGraph::builder(n_photos)
+        .edges(edges)
+        .build()
+        .unwrap();
+    Instance::new(graph, max_by_page)
+}
+
+#[test]
+fn examples() {
+    for (filename, expected) in [
+        ("examples/example1", 3),
+        ("examples/example2", 4),
+        ("examples/example3", 6),
+        ("examples/example4", 3),
+        ("examples/example5", 4),
+        ("examples/example6", 3),
+    ] {
+        let instance = read_file(filename).unwrap();
+        for method in [Method::Bitmask, Method::Reference] {
+            let solver = Solver::new(&instance).method(method);
+            assert_eq!(solver.min_pages(), Ok(expected));
+            let schedule = solver.schedule().unwrap();
+            assert_eq!(schedule.len(), expected);
+            assert!(instance.is_valid_schedule(&schedule));
+        }
+    }
+}
+
+#[test]
+fn methods_agree() {
+    let mut edges = Vec::new();
+    for u in 1..7 {
+        for v in 1..u {
+            edges.push((u, v))
+        }
+    }
+    edges.push((7, 8));
+    let instance = instance(edges, 12, 3);
+    let expected = Solver::new(&instance).method(Method::Reference).min_pages();
+    assert_eq!(expected, Ok(6));
+    assert_eq!(Solver::new(&instance).min_pages(), expected);
+}
+
+#[test]
+fn search_statistics() {
+    let graph = DependencyGraph::builder(6)
+        .edge(1, 5)
+        .weak_edges(vec![(2, 5), (2, 4), (3, 4), (4, 2)])
+        .build()
+        .unwrap();
+    let two_pages = Instance::new(graph, 3);
+    let solver = Solver::new(&two_pages).method(Method::Reference);
+    assert_eq!(solver.statistics(), Statistics::default());
+    assert_eq!(solver.min_pages(), Ok(2));
+    let statistics = solver.statistics();
+    assert!(statistics.pruned > 0 && statistics.nodes > statistics.pruned);
+    // The counters add up over the searches of a solver.
+    assert_eq!(solver.min_pages(), Ok(2));
+    assert_eq!(solver.statistics().nodes, 2 * statistics.nodes);
+    let solver = Solver::new(&two_pages);
+    assert_eq!(solver.min_pages(), Ok(2));
+    assert!(solver.statistics().nodes > 0);
+    assert_eq!(solver.statistics().pruned, 0);
+}
+
+#[test]
+fn heuristic() {
+    // Two hundred photos, each one after the photos four and five places before it.
+    let edges = (5..200).flat_map(|i| [(i - 4, i + 1), (i - 3, i + 1)]);
+    let album = instance(edges.collect(), 200, 3);
+    let solver = Solver::new(&album).method(Method::Heuristic);
+    let schedule = solver.schedule().unwrap();
+    assert!(album.is_valid_schedule(&schedule));
+    let lower_bound = solver.lower_bound().unwrap();
+    assert!(lower_bound <= schedule.len());
+    assert_eq!(solver.min_pages(), Ok(schedule.len()));
+    assert_eq!(lower_bound, 67);
+    assert!(matches!(
+        Solver::new(&album).min_pages(),
+        Err(SolveError::TooManyPhotos { .. })
+    ));
+    // Photo 2 is pinned to the first page but comes after photo 1.
+    let mut pinned = instance(vec![(1, 2)], 2, 2);
+    pinned.pins.insert(2, Pin::exactly(1));
+    assert_eq!(
+        Solver::new(&pinned).method(Method::Heuristic).min_pages(),
+        Err(SolveError::HeuristicFailed)
+    );
+    // The counts, sweeps and fits need the optimum, and do not use the heuristic.
+    let edges = (5..20).flat_map(|i| [(i - 4, i + 1), (i - 3, i + 1)]);
+    let album = instance(edges.collect(), 20, 3);
+    let heuristic = Solver::new(&album).method(Method::Heuristic);
+    let exact = Solver::new(&album);
+    assert_eq!(heuristic.count_optimal(), exact.count_optimal());
+    assert_eq!(heuristic.sweep(&[2, 3, 4]), exact.sweep(&[2, 3, 4]));
+    assert_eq!(heuristic.min_capacity(6), exact.min_capacity(6));
+    assert!(heuristic.statistics().nodes > 0);
+}
+
+#[test]
+fn anytime() {
+    let edges = (5..30).flat_map(|i| [(i - 4, i + 1), (i - 3, i + 1)]);
+    let album = instance(edges.collect(), 30, 3);
+    let n_pages = Solver::new(&album).min_pages().unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        // A search that ends proves its schedule optimal.
+        let solution = Solver::new(&album).method(method).solve().unwrap();
+        assert!(solution.optimal && album.is_valid_schedule(&solution.schedule));
+        assert_eq!(
+            (solution.schedule.len(), solution.lower_bound),
+            (n_pages, n_pages)
+        );
+        // Stopped at once, it still has a schedule.
+        for solver in [
+            Solver::new(&album).method(method).node_budget(0),
+            Solver::new(&album)
+                .method(method)
+                .time_limit(Duration::ZERO),
+        ] {
+            let solution = solver.solve().unwrap();
+            assert!(album.is_valid_schedule(&solution.schedule));
+            assert!(solution.lower_bound <= n_pages && n_pages <= solution.schedule.len());
+            assert_eq!(
+                solution.optimal,
+                solution.lower_bound == solution.schedule.len()
+            );
+        }
+    }
+    // Cancelled from another thread before the search starts.
+    let solver = Solver::new(&album);
+    let cancel = solver.cancel_handle();
+    std::thread::spawn(move || cancel.cancel()).join().unwrap();
+    let solution = solver.solve().unwrap();
+    assert!(album.is_valid_schedule(&solution.schedule));
+    assert_eq!(solver.statistics().nodes, 0);
+    // Beyond the bitmask method, the budget bounds the branch and bound.
+    let edges = (5..200).flat_map(|i| [(i - 4, i + 1), (i - 3, i + 1)]);
+    let large = instance(edges.collect(), 200, 3);
+    let solver = Solver::new(&large)
+        .method(Method::Reference)
+        .node_budget(100);
+    let solution = solver.solve().unwrap();
+    assert!(large.is_valid_schedule(&solution.schedule));
+    assert_eq!(solution.lower_bound, 67);
+    assert!(solver.statistics().nodes <= 100);
+    // Too many photos ready at once to try every page, but the heuristic reaches the bound.
+    let edges = (1..=150)
+        .map(|i| (2 * i - 1, 2 * i))
+        .chain([(1, 4), (3, 2)]);
+    let pairs = instance(edges.collect(), 300, 4);
+    let solution = Solver::new(&pairs)
+        .method(Method::Reference)
+        .time_limit(Duration::from_secs(2))
+        .solve()
+        .unwrap();
+    assert!(pairs.is_valid_schedule(&solution.schedule));
+    assert!(solution.optimal && solution.schedule.len() == 75);
+    // Photo 2 is pinned to the first page but comes after photo 1.
+    let mut pinned = instance(vec![(1, 2)], 2, 2);
+    pinned.pins.insert(2, Pin::exactly(1));
+    assert_eq!(
+        Solver::new(&pinned).node_budget(0).solve(),
+        Err(SolveError::Stopped)
+    );
+    assert_eq!(
+        Solver::new(&pinned).solve(),
+        Err(SolveError::PinsUnsatisfiable)
+    );
+}
+
+#[test]
+fn errors() {
+    let cycle = instance(vec![(1, 2), (2, 3), (3, 1)], 4, 2);
+    let error = Solver::new(&cycle).schedule().unwrap_err();
+    assert_eq!(
+        error,
+        SolveError::Cyclic {
+            cycle: vec![1, 2, 3]
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "the constraints contain the cycle 1 -> 2 -> 3 -> 1"
+    );
+    let no_room = instance(vec![], 2, 0);
+    assert_eq!(
+        Solver::new(&no_room).min_pages(),
+        Err(SolveError::ZeroCapacity)
+    );
+    // Photo 3 has two predecessors and two successors, so the graph is no forest.
+    let too_many = instance(vec![(1, 3), (2, 3), (3, 4), (3, 5)], 70, 3);
+    assert!(matches!(
+        Solver::new(&too_many).min_pages(),
+        Err(SolveError::TooManyPhotos { .. })
+    ));
+    assert_eq!(
+        Solver::new(&too_many).method(Method::Reference).min_pages(),
+        Ok(24)
+    );
+    // A forest is solved by Hu's algorithm, whatever its number of photos.
+    let forest = instance(vec![], 70, 2);
+    assert_eq!(Solver::new(&forest).min_pages(), Ok(35));
+    assert_eq!(
+        DependencyGraph::builder(2).edge(0, 1).build(),
+        Err(GraphError::PhotoOutOfRange {
+            photo: 0,
+            n_photos: 2
+        })
+    );
+    assert!(matches!(
+        parse("2 1 1\n1 3\n".as_bytes()),
+        Err(ParseError::PhotoOutOfRange { photo: 3, .. })
+    ));
+}
+
+#[test]
+fn repair_impossible_instance() {
+    let edges = vec![(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4), (5, 6)];
+    let impossible = instance(edges, 6, 2);
+    let (repaired, dropped) = impossible.repaired();
+    assert!(dropped.exact);
+    assert_eq!(dropped.edges.len(), 2);
+    assert_eq!(repaired.graph.edges().count(), 5);
+    let schedule = Solver::new(&repaired).schedule().unwrap();
+    assert!(repaired.is_valid_schedule(&schedule));
+    assert!(!impossible.is_valid_schedule(&schedule));
+}
+
+#[test]
+fn variable_capacities() {
+    // A cover page with one photo, a two-photo intro, then four photos by page.
+    let graph = DependencyGraph::builder(9).build().unwrap();
+    let capacities = Capacities::new(vec![1, 2], vec![4]);
+    let instance = Instance::with_capacities(graph, capacities);
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule.len(), 4);
+        assert!(instance.is_valid_schedule(&schedule));
+    }
+    let chain = DependencyGraph::builder(4)
+        .edges(vec![(1, 2), (2, 3)])
+        .build()
+        .unwrap();
+    let instance = Instance::with_capacities(chain, Capacities::new(vec![], vec![1, 3]));
+    assert_eq!(Solver::new(&instance).min_pages(), Ok(3));
+}
+
+#[test]
+fn together_groups() {
+    // A burst of three photos after photo 4, photo 5 after the burst.
+    let graph = DependencyGraph::builder(5)
+        .together(&[1, 2, 3])
+        .edges(vec![(4, 1), (3, 5)])
+        .build()
+        .unwrap();
+    let instance = Instance::new(graph, 3);
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![4], vec![1, 2, 3], vec![5]]);
+    }
+    let too_large = Instance::new(instance.graph.clone(), 2);
+    assert_eq!(
+        Solver::new(&too_large).min_pages(),
+        Err(SolveError::GroupTooLarge {
+            photos: vec![1, 2, 3],
+            size: 3,
+            capacity: 2
+        })
+    );
+    let separated = DependencyGraph::builder(4)
+        .together(&[1, 2])
+        .edges(vec![(1, 3), (3, 2)])
+        .build()
+        .unwrap();
+    let error = Solver::new(&Instance::new(separated, 2))
+        .min_pages()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "photos 1, 2 must share a page but the constraints 1 -> 3 -> 2 separate them"
+    );
+}
+
+#[test]
+fn apart_pairs() {
+    // Four unconstrained photos, two pages of three, but 1, 2 and 3 pairwise apart.
+    let graph = DependencyGraph::builder(4)
+        .apart(1, 2)
+        .apart(2, 3)
+        .apart(1, 3)
+        .build()
+        .unwrap();
+    let instance = Instance::new(graph, 3);
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule.len(), 3);
+        assert!(instance.is_valid_schedule(&schedule));
+    }
+    let conflict = DependencyGraph::builder(3)
+        .together(&[1, 2, 3])
+        .apart(1, 3)
+        .build()
+        .unwrap();
+    let error = Solver::new(&Instance::new(conflict, 3))
+        .min_pages()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "photos 1 and 3 must be apart but photos 1, 2, 3 must share a page"
+    );
+}
+
+#[test]
+fn pinned_photos() {
+    // The cover photo 1 alone on page 1, the group shot 5 on the last page,
+    // and photo 4 not before page 3.
+    let graph = DependencyGraph::builder(5)
+        .edges(vec![(2, 3)])
+        .last(5)
+        .build()
+        .unwrap();
+    let mut instance = Instance::new(graph, 2);
+    instance.pins.insert(1, Pin::exactly(1));
+    instance.pins.insert(4, Pin::release(3));
+    instance.capacities = Capacities::new(vec![1], vec![2]);
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![1], vec![2], vec![3, 4], vec![5]]);
+    }
+    instance.pins.insert(3, Pin::deadline(2));
+    assert_eq!(
+        Solver::new(&instance).min_pages(),
+        Err(SolveError::PinsUnsatisfiable)
+    );
+    instance
+        .pins
+        .insert(3, Pin::release(3).and(Pin::deadline(2)));
+    let error = Solver::new(&instance).min_pages().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "photo 3 cannot be pinned to pages 3 to 2"
+    );
+}
+
+#[test]
+fn lagged_edges() {
+    // Photo 2 at least three pages after photo 1, and photo 4 not before photo 2.
+    let instance = parse("4 2 2\n1 2 3\n2 4 0\n".as_bytes()).unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![1, 3], vec![], vec![], vec![2, 4]]);
+    }
+    let strict = parse("4 2 2\n1 2 1\n2 4 0\n".as_bytes()).unwrap();
+    assert_eq!(Solver::new(&strict).min_pages(), Ok(2));
+}
+
+#[test]
+fn chapters() {
+    // Photos 1 to 4 and photos 5 to 7 are two chapters, without edges between them.
+    let input = "7 2 3\n1 2\n2 3\n5 6\nchapter 1 2 3 4\nchapter 5 6 7\n";
+    let mut instance = parse(input.as_bytes()).unwrap();
+    let expected = vec![vec![1, 4], vec![2], vec![3], vec![5, 7], vec![6]];
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, expected);
+    }
+    // With an edge from the second chapter to the first one, they are solved together.
+    instance.graph = DependencyGraph::builder(7)
+        .edges(vec![(1, 2), (2, 3), (5, 6), (7, 4)])
+        .build()
+        .unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule.len(), 5);
+        assert!(instance.is_valid_schedule(&schedule));
+    }
+    instance.graph = DependencyGraph::builder(7)
+        .edges(vec![(1, 5), (6, 2)])
+        .build()
+        .unwrap();
+    assert_eq!(
+        Solver::new(&instance).min_pages(),
+        Err(SolveError::ChaptersUnsatisfiable)
+    );
+    // Photo 3, in no chapter, has a page of its own in the middle of the chapter of 1 and 2,
+    // but not in a chapter of its own.
+    let input = "3 1 2\n1 3\n3 2\nchapter 1 2\n";
+    let instance = parse(input.as_bytes()).unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![1], vec![3], vec![2]]);
+    }
+    assert!(instance.is_valid_schedule(&[vec![1], vec![3], vec![2]]));
+    let instance = parse(format!("{}chapter 3\n", input).as_bytes()).unwrap();
+    assert!(!instance.is_valid_schedule(&[vec![1], vec![3], vec![2]]));
+    assert_eq!(
+        Solver::new(&instance).min_pages(),
+        Err(SolveError::ChaptersUnsatisfiable)
+    );
+}
+
+#[test]
+fn spreads() {
+    // A chain of four photos, one by page, with photos 2 and 3 facing each other.
+    let input = "4 1 3\n1 2\n2 3\n3 4\nspread_together 2 3\n";
+    let mut instance = parse(input.as_bytes()).unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![1], vec![2], vec![3], vec![4]]);
+    }
+    // With page 1 facing page 2, a page stays empty before photo 2.
+    instance.spreads.as_mut().unwrap().first_alone = false;
+    for method in [Method::Bitmask, Method::Reference] {
+        let schedule = Solver::new(&instance).method(method).schedule().unwrap();
+        assert_eq!(schedule, vec![vec![1], vec![], vec![2], vec![3], vec![4]]);
+    }
+    let input = "2 2 0\nspreads paired\nspread_apart 1 2\n";
+    let instance = parse(input.as_bytes()).unwrap();
+    assert_eq!(Solver::new(&instance).min_pages(), Ok(3));
+    let input = "4 1 3\n1 2\n2 3\n3 4\nspread_together 1 4\n";
+    let instance = parse(input.as_bytes()).unwrap();
+    assert_eq!(
+        Solver::new(&instance).min_pages(),
+        Err(SolveError::SpreadsUnsatisfiable)
+    );
+}
+
+#[test]
+fn all_schedules() {
+    // Three photos without constraints, two by page: either page can hold the pair.
+    let instance = instance(vec![], 3, 2);
+    let schedules: Vec<_> = Solver::new(&instance).schedules().unwrap().collect();
+    assert_eq!(schedules.len(), 6);
+    assert!(schedules.contains(&vec![vec![1, 3], vec![2]]));
+    assert!(schedules.contains(&vec![vec![2], vec![1, 3]]));
+    let example1 = read_file("examples/example1").unwrap();
+    let schedules: Vec<_> = Solver::new(&example1).schedules().unwrap().collect();
+    assert_eq!(schedules, vec![vec![vec![2, 3], vec![1], vec![4]]]);
+}
+
+#[test]
+fn counts() {
+    // 11 photos without constraints, two by page: the single photo goes on any of the
+    // 6 pages and the others are paired in 11! / 2^5 ways.
+    let example3 = read_file("examples/example3").unwrap();
+    let solver = Solver::new(&example3);
+    assert_eq!(solver.count_optimal(), Ok(Count::from(7_484_400)));
+    assert_eq!(solver.count_within(5), Ok(Count::zero()));
+    let example1 = read_file("examples/example1").unwrap();
+    assert_eq!(Solver::new(&example1).count_optimal(), Ok(Count::one()));
+    assert_eq!(Solver::new(&example1).count_within(4), Ok(Count::from(6)));
+    // Ten layers of five photos, one by page, each layer before the next one:
+    // the photos of each layer come in any order, so 120^10 schedules.
+    let mut edges = Vec::new();
+    for layer in 0..9 {
+        for u in 5 * layer + 1..=5 * layer + 5 {
+            for v in 5 * layer + 6..=5 * layer + 10 {
+                edges.push((u, v));
+            }
+        }
+    }
+    let layers = instance(edges, 50, 1);
+    let count = Solver::new(&layers).count_optimal().unwrap();
+    assert_eq!(count.to_u64(), None);
+    assert_eq!(count.to_string(), "619173642240000000000");
+}
+
+#[test]
+fn sample() {
+    let instance = read_file("examples/example3").unwrap();
+    let solver = Solver::new(&instance);
+    let schedule = solver.sample(42).unwrap();
+    assert_eq!(schedule.len(), 6);
+    assert!(instance.is_valid_schedule(&schedule));
+    // The same seed draws the same schedule, and drafts vary with the seed.
+    assert_eq!(solver.sample(42), Ok(schedule.clone()));
+    assert!((0..10).any(|seed| solver.sample(seed).unwrap() != schedule));
+}
+
+#[test]
+fn capacity_sweep() {
+    // 11 photos without constraints.
+    let example3 = read_file("examples/example3").unwrap();
+    let solver = Solver::new(&example3);
+    let capacities: Vec<_> = (1..=11).collect();
+    let pages: Vec<_> = solver.sweep(&capacities).into_iter().flatten().collect();
+    assert_eq!(pages, vec![11, 6, 4, 3, 3, 2, 2, 2, 2, 2, 1]);
+    // The same as solving each capacity on its own, in any order.
+    let mut edges = vec![(1, 2), (2, 3), (3, 4), (5, 6)];
+    edges.extend((7..12).map(|v| (4, v)));
+    let chains = instance(edges, 12, 1);
+    let capacities = [6, 2, 0, 3, 1, 12];
+    let expected: Vec<_> = capacities
+        .iter()
+        .map(|&max_by_page| {
+            let mut instance = chains.clone();
+            instance.capacities = Capacities::uniform(max_by_page);
+            Solver::new(&instance).min_pages()
+        })
+        .collect();
+    assert_eq!(Solver::new(&chains).sweep(&capacities), expected);
+    assert_eq!(expected[2], Err(SolveError::ZeroCapacity));
+    // The inverse query.
+    let example1 = read_file("examples/example1").unwrap();
+    assert_eq!(Solver::new(&example1).min_capacity(4), Ok(Some(1)));
+    assert_eq!(Solver::new(&example1).min_capacity(3), Ok(Some(2)));
+    assert_eq!(Solver::new(&example1).min_capacity(2), Ok(None));
+    assert_eq!(solver.min_capacity(3), Ok(Some(4)));
+    assert_eq!(solver.page_multiple(4).min_capacity(4), Ok(Some(3)));
+}
+
+#[test]
+fn page_multiple() {
+    // 11 photos without constraints, two by page: 6 pages, rounded up to 8.
+    let instance = read_file("examples/example3").unwrap();
+    for method in [Method::Bitmask, Method::Reference] {
+        let solver = Solver::new(&instance).method(method).page_multiple(4);
+        assert_eq!(solver.min_pages(), Ok(8));
+        let schedule = solver.schedule().unwrap();
+        assert_eq!(schedule.len(), 8);
+        assert!(instance.is_valid_schedule(&schedule));
+        let sizes: Vec<_> = schedule.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 2, 2, 1, 1, 1, 1, 1]);
+    }
+    assert_eq!(Solver::new(&instance).page_multiple(2).min_pages(), Ok(6));
+    assert_eq!(
+        Solver::new(&instance).page_multiple(0).min_pages(),
+        Err(SolveError::ZeroPageMultiple)
+    );
+}